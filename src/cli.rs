@@ -0,0 +1,139 @@
+use thiserror::Error;
+
+// Repositories untouched for longer than this are listed by `popi stale`
+pub const DEFAULT_STALE_DAYS: u64 = 90;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CliArgs {
+  // Ignore the on-disk index and scan every root again
+  pub rebuild_index: bool,
+  pub command: Command,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Command {
+  // Open the picker
+  #[default]
+  Pick,
+  // `popi stale`, report repositories nobody has worked on for a while
+  Stale(StaleArgs),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StaleArgs {
+  pub days: u64,
+  // Print JSON instead of a table
+  pub json: bool,
+}
+
+impl Default for StaleArgs {
+  fn default() -> Self {
+    StaleArgs {
+      days: DEFAULT_STALE_DAYS,
+      json: false,
+    }
+  }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CliError {
+  #[error("Unknown argument: {0}")]
+  UnknownArgument(String),
+  #[error("Missing value for {0}")]
+  MissingValue(String),
+  #[error("Invalid value for {flag}: {value}")]
+  InvalidValue { flag: String, value: String },
+}
+
+impl CliArgs {
+  pub fn from_env() -> Result<Self, CliError> {
+    Self::parse(std::env::args().skip(1))
+  }
+
+  pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, CliError> {
+    let mut cli_args = CliArgs::default();
+    let mut args = args.peekable();
+    if args.peek().map(String::as_str) == Some("stale") {
+      args.next();
+      cli_args.command = Command::Stale(StaleArgs::default());
+    }
+    while let Some(arg) = args.next() {
+      match (arg.as_str(), &mut cli_args.command) {
+        ("--rebuild-index", _) => cli_args.rebuild_index = true,
+        ("--days", Command::Stale(stale)) => {
+          let value = args
+            .next()
+            .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+          stale.days = value
+            .parse()
+            .map_err(|_| CliError::InvalidValue { flag: arg, value })?;
+        }
+        ("--json", Command::Stale(stale)) => stale.json = true,
+        _ => return Err(CliError::UnknownArgument(arg)),
+      }
+    }
+    Ok(cli_args)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str]) -> Result<CliArgs, CliError> {
+    CliArgs::parse(args.iter().map(|arg| arg.to_string()))
+  }
+
+  #[test]
+  fn parse_flags() {
+    assert_eq!(parse(&[]), Ok(CliArgs::default()));
+    assert_eq!(
+      parse(&["--rebuild-index"]),
+      Ok(CliArgs {
+        rebuild_index: true,
+        command: Command::Pick,
+      })
+    );
+    assert_eq!(
+      parse(&["--unknown"]),
+      Err(CliError::UnknownArgument("--unknown".to_string()))
+    );
+  }
+
+  #[test]
+  fn parse_stale_command() {
+    assert_eq!(
+      parse(&["stale"]),
+      Ok(CliArgs {
+        rebuild_index: false,
+        command: Command::Stale(StaleArgs::default()),
+      })
+    );
+    assert_eq!(
+      parse(&["stale", "--days", "30", "--json"]),
+      Ok(CliArgs {
+        rebuild_index: false,
+        command: Command::Stale(StaleArgs {
+          days: 30,
+          json: true
+        }),
+      })
+    );
+    assert_eq!(
+      parse(&["stale", "--days"]),
+      Err(CliError::MissingValue("--days".to_string()))
+    );
+    assert_eq!(
+      parse(&["stale", "--days", "soon"]),
+      Err(CliError::InvalidValue {
+        flag: "--days".to_string(),
+        value: "soon".to_string()
+      })
+    );
+    // Only the subcommand takes these
+    assert_eq!(
+      parse(&["--json"]),
+      Err(CliError::UnknownArgument("--json".to_string()))
+    );
+  }
+}
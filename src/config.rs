@@ -1,22 +1,104 @@
 use dirs::config_local_dir;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
-use yaml_rust::YamlLoader;
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::ghq::{detect_ghq_roots, GhqEnvironment};
 
 #[derive(Debug)]
 pub struct LocalStorage {
   pub root_path: PathBuf,
-  pub repo_paths: Vec<PathBuf>,
+  pub repo_paths: Vec<RepoRoot>,
+  pub finder_options: FinderOptions,
+}
+
+pub const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FinderOptions {
+  // List submodules from `.gitmodules` as children of their superproject
+  pub submodules: bool,
+  // List members of Cargo, npm and pnpm workspaces as children of their repository
+  pub workspaces: bool,
+  // Give up on a root after this long, unless the root sets its own timeout
+  pub scan_timeout: Duration,
+  // List entries under dot-prefixed directories like `.cache`, can be toggled in the picker
+  pub show_hidden: bool,
+  // Also list folders recently opened in VS Code and JetBrains IDEs
+  pub recent_projects: bool,
+  // Repositories at or below these paths count as archived, like ones with a marker
+  pub archived: Vec<PathBuf>,
+  // List archived repositories along with the others, can be toggled in the picker
+  pub show_archived: bool,
+}
+
+impl Default for FinderOptions {
+  fn default() -> Self {
+    FinderOptions {
+      submodules: false,
+      workspaces: false,
+      scan_timeout: DEFAULT_SCAN_TIMEOUT,
+      show_hidden: false,
+      recent_projects: false,
+      archived: vec![],
+      show_archived: false,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RootLayout {
+  // Every entry directly under the root is a repository.
+  Flat,
+  // ghq-style tree, repositories live at `host/owner/name`.
+  Ghq,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepoRoot {
+  pub path: PathBuf,
+  pub layout: RootLayout,
+  // Overrides FinderOptions.scan_timeout, e.g. for a slow network mount
+  pub timeout: Option<Duration>,
+}
+
+impl RepoRoot {
+  pub fn new(path: PathBuf, layout: RootLayout) -> Self {
+    RepoRoot {
+      path,
+      layout,
+      timeout: None,
+    }
+  }
+
+  pub fn timeout(&self, options: &FinderOptions) -> Duration {
+    self.timeout.unwrap_or(options.scan_timeout)
+  }
+}
+
+impl From<PathBuf> for RepoRoot {
+  fn from(path: PathBuf) -> Self {
+    RepoRoot::new(path, RootLayout::Flat)
+  }
 }
 
 impl LocalStorage {
   pub fn new() -> Result<Self, LoadConfigError> {
     let mut dir = config_local_dir().unwrap();
     dir.push("popi");
-    Self::new_from_root_path(dir)
+    Self::new_with_ghq_detection(dir, &GhqEnvironment::from_process())
   }
   pub fn new_from_root_path(root_path: PathBuf) -> Result<Self, LoadConfigError> {
-    load_localstorage(root_path)
+    load_localstorage(root_path, None)
+  }
+  // Same as new_from_root_path, but ghq roots found in the environment are added as well.
+  // Works even without config.yml as long as some ghq root is detected.
+  pub fn new_with_ghq_detection(
+    root_path: PathBuf,
+    ghq_env: &GhqEnvironment,
+  ) -> Result<Self, LoadConfigError> {
+    load_localstorage(root_path, Some(ghq_env))
   }
 }
 
@@ -30,7 +112,7 @@ fn read_file_with_priority(
     config_path_reading.push(file_name);
     match std::fs::read_to_string(&config_path_reading) {
       Ok(content) => {
-        return Ok((content, config_path_reading.to_str().unwrap().to_string()));
+        return Ok((content, config_path_reading.to_string_lossy().into_owned()));
       }
       Err(_) => continue,
     }
@@ -49,13 +131,27 @@ pub enum LoadConfigError {
   ConfigInvalidYamlFormat { config_yml_path: String },
 }
 
-fn load_localstorage(root_path: PathBuf) -> Result<LocalStorage, LoadConfigError> {
+fn load_localstorage(
+  root_path: PathBuf,
+  ghq_env: Option<&GhqEnvironment>,
+) -> Result<LocalStorage, LoadConfigError> {
   let (config_yml, config_yml_path) =
-    read_file_with_priority(&root_path, vec!["config.yml", "config.yaml"]).map_err(|_| {
-      LoadConfigError::NoConfigFileFound {
-        root_path: root_path.to_path_buf(),
+    match read_file_with_priority(&root_path, vec!["config.yml", "config.yaml"]) {
+      Ok(file) => file,
+      Err(_) => {
+        let ghq_roots = ghq_env.map(detect_ghq_roots).unwrap_or_default();
+        if ghq_roots.is_empty() {
+          return Err(LoadConfigError::NoConfigFileFound { root_path });
+        }
+        let mut repo_paths = vec![];
+        add_ghq_roots(&mut repo_paths, ghq_roots);
+        return Ok(LocalStorage {
+          root_path,
+          repo_paths,
+          finder_options: FinderOptions::default(),
+        });
       }
-    })?;
+    };
 
   let config_payload = YamlLoader::load_from_str(&config_yml).map_err(|_| {
     LoadConfigError::ConfigInvalidYamlFormat {
@@ -63,29 +159,113 @@ fn load_localstorage(root_path: PathBuf) -> Result<LocalStorage, LoadConfigError
     }
   })?;
 
-  let repos = &config_payload
-    .get(0)
+  let config = config_payload
+    .first()
     .ok_or(LoadConfigError::ConfigInvalidYamlFormat {
       config_yml_path: config_yml_path.to_string(),
-    })?["repos"]
+    })?;
+  let repos = config["repos"]
     .as_vec()
     .ok_or(LoadConfigError::ConfigInvalidYamlFormat {
       config_yml_path: config_yml_path.to_string(),
     })?;
-  let repo_paths = &repos
+  let mut repo_paths = repos
     .iter()
     .map(|repo| {
-      let repo_path = repo
-        .as_str()
-        .ok_or(LoadConfigError::ConfigInvalidYamlFormat {
-          config_yml_path: config_yml_path.to_string(),
-        })?;
-      Ok(PathBuf::from(repo_path))
+      parse_repo_root(repo).ok_or(LoadConfigError::ConfigInvalidYamlFormat {
+        config_yml_path: config_yml_path.to_string(),
+      })
     })
-    .collect::<Result<Vec<PathBuf>, LoadConfigError>>()?;
+    .collect::<Result<Vec<RepoRoot>, LoadConfigError>>()?;
+
+  let invalid_format = || LoadConfigError::ConfigInvalidYamlFormat {
+    config_yml_path: config_yml_path.to_string(),
+  };
+  // Only a config without roots of its own picks up ghq roots unless asked to, adding them
+  // to a list someone wrote by hand would change what existing configs scan
+  let detect_ghq =
+    optional_bool(&config["detect_ghq"], repo_paths.is_empty()).ok_or_else(invalid_format)?;
+  let finder_options = FinderOptions {
+    submodules: optional_bool(&config["submodules"], false).ok_or_else(invalid_format)?,
+    workspaces: optional_bool(&config["workspaces"], false).ok_or_else(invalid_format)?,
+    scan_timeout: optional_duration(&config["scan_timeout"])
+      .ok_or_else(invalid_format)?
+      .unwrap_or(DEFAULT_SCAN_TIMEOUT),
+    show_hidden: optional_bool(&config["show_hidden"], false).ok_or_else(invalid_format)?,
+    recent_projects: optional_bool(&config["recent_projects"], false).ok_or_else(invalid_format)?,
+    archived: optional_paths(&config["archived"]).ok_or_else(invalid_format)?,
+    show_archived: optional_bool(&config["show_archived"], false).ok_or_else(invalid_format)?,
+  };
+  if let (true, Some(ghq_env)) = (detect_ghq, ghq_env) {
+    add_ghq_roots(&mut repo_paths, detect_ghq_roots(ghq_env));
+  }
 
   Ok(LocalStorage {
     root_path,
-    repo_paths: repo_paths.clone(),
+    repo_paths,
+    finder_options,
+  })
+}
+
+// Seconds as an integer or a float, must be positive
+fn optional_duration(value: &Yaml) -> Option<Option<Duration>> {
+  let seconds = match value {
+    Yaml::BadValue => return Some(None),
+    Yaml::Integer(seconds) => *seconds as f64,
+    value => value.as_f64()?,
+  };
+  if seconds.is_finite() && seconds > 0.0 {
+    Some(Some(Duration::from_secs_f64(seconds)))
+  } else {
+    None
+  }
+}
+
+// Missing keys fall back to the default, anything other than a bool is invalid
+fn optional_bool(value: &Yaml, default: bool) -> Option<bool> {
+  match value {
+    Yaml::BadValue => Some(default),
+    value => value.as_bool(),
+  }
+}
+
+// A missing key is an empty list, otherwise a list of path strings
+fn optional_paths(value: &Yaml) -> Option<Vec<PathBuf>> {
+  match value {
+    Yaml::BadValue => Some(vec![]),
+    value => value
+      .as_vec()?
+      .iter()
+      .map(|path| path.as_str().map(PathBuf::from))
+      .collect(),
+  }
+}
+
+// Roots already listed in config.yml win, so a user can override the layout
+fn add_ghq_roots(repo_paths: &mut Vec<RepoRoot>, ghq_roots: Vec<PathBuf>) {
+  for path in ghq_roots {
+    if !repo_paths.iter().any(|root| root.path == path) {
+      repo_paths.push(RepoRoot::new(path, RootLayout::Ghq));
+    }
+  }
+}
+
+// A root is either a plain path string, or a mapping with `path` and optional `layout`, `timeout`
+fn parse_repo_root(repo: &Yaml) -> Option<RepoRoot> {
+  if let Some(path) = repo.as_str() {
+    return Some(PathBuf::from(path).into());
+  }
+  let path = repo["path"].as_str()?;
+  let layout = match &repo["layout"] {
+    Yaml::BadValue => RootLayout::Flat,
+    layout => match layout.as_str()? {
+      "flat" => RootLayout::Flat,
+      "ghq" => RootLayout::Ghq,
+      _ => return None,
+    },
+  };
+  Some(RepoRoot {
+    timeout: optional_duration(&repo["timeout"])?,
+    ..RepoRoot::new(PathBuf::from(path), layout)
   })
 }
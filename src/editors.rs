@@ -0,0 +1,472 @@
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::Chars;
+
+use crate::finder::{is_dir, read_entries, Repo, RepoStatus};
+use crate::project_type::detect_project_types;
+use crate::source::{RepoSource, SourceFuture};
+
+// VS Code and forks sharing its storage format, as named under the config directory
+const VSCODE_DIRS: &[&str] = &["Code", "Code - Insiders", "VSCodium"];
+
+// Where `storage.json` keeps recently opened folders, depending on the version.
+// `*` stands for every item of an array.
+const RECENT_FOLDER_KEYS: &[&[&str]] = &[
+  &["openedPathsList", "entries", "*", "folderUri"],
+  &["openedPathsList", "workspaces3", "*"],
+  &["windowsState", "lastActiveWindow", "folder"],
+  &["windowsState", "openedWindows", "*", "folder"],
+  &["backupWorkspaces", "folders", "*", "folderUri"],
+];
+
+// Where editors keep their state. Kept separate from the process environment so it can be
+// pointed at fixtures.
+#[derive(Clone, Debug, Default)]
+pub struct EditorEnvironment {
+  // `~/.config` on Linux, `~/Library/Application Support` on macOS, `%APPDATA%` on Windows
+  pub config_dir: Option<PathBuf>,
+  pub home_dir: Option<PathBuf>,
+}
+
+impl EditorEnvironment {
+  pub fn from_process() -> Self {
+    EditorEnvironment {
+      config_dir: dirs::config_dir(),
+      home_dir: dirs::home_dir(),
+    }
+  }
+}
+
+// Folders recently opened in VS Code, read from its `storage.json`
+pub struct VsCodeSource {
+  pub env: EditorEnvironment,
+}
+
+impl RepoSource for VsCodeSource {
+  fn name(&self) -> &str {
+    "vscode"
+  }
+
+  fn list(&self) -> SourceFuture<'_> {
+    Box::pin(async move {
+      let mut paths: Vec<PathBuf> = vec![];
+      for dir in VSCODE_DIRS {
+        let Some(app_dir) = self.env.config_dir.as_ref().map(|config| config.join(dir)) else {
+          continue;
+        };
+        // Newer versions moved the file into globalStorage
+        for storage in [
+          app_dir.join("User/globalStorage/storage.json"),
+          app_dir.join("storage.json"),
+        ] {
+          let Ok(content) = async_fs::read_to_string(&storage).await else {
+            continue;
+          };
+          let Some(storage) = parse_json(&content) else {
+            continue;
+          };
+          for keys in RECENT_FOLDER_KEYS {
+            paths.extend(
+              values_at(&storage, keys)
+                .into_iter()
+                .filter_map(Json::as_str)
+                .filter_map(path_from_file_uri),
+            );
+          }
+        }
+      }
+      RepoStatus::Found(repos_at(paths).await, vec![])
+    })
+  }
+}
+
+// Projects recently opened in any JetBrains IDE, read from each one's `recentProjects.xml`
+pub struct JetBrainsSource {
+  pub env: EditorEnvironment,
+}
+
+impl RepoSource for JetBrainsSource {
+  fn name(&self) -> &str {
+    "jetbrains"
+  }
+
+  fn list(&self) -> SourceFuture<'_> {
+    Box::pin(async move {
+      let mut paths: Vec<PathBuf> = vec![];
+      let Some(jetbrains_dir) = self
+        .env
+        .config_dir
+        .as_ref()
+        .map(|dir| dir.join("JetBrains"))
+      else {
+        return RepoStatus::Found(vec![], vec![]);
+      };
+      // One directory per product and version, e.g. `IntelliJIdea2024.1`
+      // Nothing to report when it can not be read, the editor may simply not be installed
+      let products = read_entries(&jetbrains_dir, &mut vec![])
+        .await
+        .unwrap_or_default();
+      for (product, _) in products {
+        let recent = product.join("options/recentProjects.xml");
+        if let Ok(content) = async_fs::read_to_string(&recent).await {
+          paths.extend(
+            xml_paths(&content)
+              .into_iter()
+              .map(|path| self.expand_user_home(&path)),
+          );
+        }
+      }
+      RepoStatus::Found(repos_at(paths).await, vec![])
+    })
+  }
+}
+
+impl JetBrainsSource {
+  fn expand_user_home(&self, path: &str) -> PathBuf {
+    match (path.strip_prefix("$USER_HOME$"), &self.env.home_dir) {
+      (Some(rest), Some(home)) => home.join(rest.trim_start_matches(['/', '\\'])),
+      _ => PathBuf::from(path),
+    }
+  }
+}
+
+// Editors remember folders long after they are gone, only existing directories are listed
+async fn repos_at(mut paths: Vec<PathBuf>) -> Vec<Repo> {
+  paths.sort();
+  paths.dedup();
+  let mut repos: Vec<Repo> = vec![];
+  for path in paths {
+    if !path.is_absolute() || !is_dir(&path).await {
+      continue;
+    }
+    let Some(name) = path.file_name().map(|name| name.to_os_string()) else {
+      continue;
+    };
+    let mut repo = Repo::new(path, name);
+    repo.project_types = detect_project_types(&repo.path).await;
+    repos.push(repo);
+  }
+  repos
+}
+
+// Just enough of JSON to find the recent folders in `storage.json`
+#[derive(Debug, PartialEq)]
+enum Json {
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+  // Numbers, booleans and null, nothing popi looks at
+  Other,
+}
+
+impl Json {
+  fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(string) => Some(string),
+      _ => None,
+    }
+  }
+}
+
+// Values at a path of object keys, see RECENT_FOLDER_KEYS
+fn values_at<'a>(json: &'a Json, keys: &[&str]) -> Vec<&'a Json> {
+  match (keys.split_first(), json) {
+    (None, _) => vec![json],
+    (Some((&"*", rest)), Json::Array(items)) => items
+      .iter()
+      .flat_map(|item| values_at(item, rest))
+      .collect(),
+    (Some((key, rest)), Json::Object(fields)) => fields
+      .iter()
+      .filter(|(name, _)| name == key)
+      .flat_map(|(_, value)| values_at(value, rest))
+      .collect(),
+    _ => vec![],
+  }
+}
+
+fn parse_json(content: &str) -> Option<Json> {
+  parse_json_value(&mut content.chars().peekable())
+}
+
+fn parse_json_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+  skip_whitespace(chars);
+  match chars.peek()? {
+    '"' => {
+      chars.next();
+      parse_json_string(chars).map(Json::String)
+    }
+    '[' => {
+      chars.next();
+      let mut items: Vec<Json> = vec![];
+      skip_whitespace(chars);
+      if chars.next_if_eq(&']').is_some() {
+        return Some(Json::Array(items));
+      }
+      loop {
+        items.push(parse_json_value(chars)?);
+        skip_whitespace(chars);
+        match chars.next()? {
+          ',' => continue,
+          ']' => return Some(Json::Array(items)),
+          _ => return None,
+        }
+      }
+    }
+    '{' => {
+      chars.next();
+      let mut fields: Vec<(String, Json)> = vec![];
+      skip_whitespace(chars);
+      if chars.next_if_eq(&'}').is_some() {
+        return Some(Json::Object(fields));
+      }
+      loop {
+        skip_whitespace(chars);
+        chars.next_if_eq(&'"')?;
+        let key = parse_json_string(chars)?;
+        skip_whitespace(chars);
+        chars.next_if_eq(&':')?;
+        fields.push((key, parse_json_value(chars)?));
+        skip_whitespace(chars);
+        match chars.next()? {
+          ',' => continue,
+          '}' => return Some(Json::Object(fields)),
+          _ => return None,
+        }
+      }
+    }
+    _ => {
+      while chars
+        .next_if(|c| !matches!(c, ',' | ']' | '}') && !c.is_whitespace())
+        .is_some()
+      {}
+      Some(Json::Other)
+    }
+  }
+}
+
+// The rest of a string after its opening quote
+fn parse_json_string(chars: &mut Peekable<Chars>) -> Option<String> {
+  let mut string = String::new();
+  loop {
+    match chars.next()? {
+      '"' => return Some(string),
+      '\\' => match chars.next()? {
+        'n' => string.push('\n'),
+        't' => string.push('\t'),
+        'r' => string.push('\r'),
+        'b' => string.push('\u{8}'),
+        'f' => string.push('\u{c}'),
+        'u' => {
+          let code = chars.by_ref().take(4).collect::<String>();
+          if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+            string.push(c);
+          }
+        }
+        other => string.push(other),
+      },
+      c => string.push(c),
+    }
+  }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+  while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+// `file:///home/me/my%20app` to `/home/me/my app`, None for anything not on this machine
+fn path_from_file_uri(uri: &str) -> Option<PathBuf> {
+  let rest = uri.strip_prefix("file://")?;
+  let (authority, path) = rest.split_at(rest.find('/')?);
+  if !authority.is_empty() && authority != "localhost" {
+    return None;
+  }
+  let bytes = percent_decode(path)?;
+  // `/c:/Users/me` on Windows
+  #[cfg(windows)]
+  let bytes = match bytes.get(2) {
+    Some(b':') => bytes[1..].to_vec(),
+    _ => bytes,
+  };
+  #[cfg(unix)]
+  {
+    use std::os::unix::ffi::OsStringExt;
+    Some(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+  }
+  #[cfg(not(unix))]
+  {
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+  }
+}
+
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+  let mut bytes: Vec<u8> = vec![];
+  let mut input = text.bytes();
+  while let Some(byte) = input.next() {
+    if byte == b'%' {
+      let hex = [input.next()?, input.next()?];
+      bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+    } else {
+      bytes.push(byte);
+    }
+  }
+  Some(bytes)
+}
+
+// Project paths in `recentProjects.xml`: keys of the `additionalInfo` map in current versions,
+// `recentPaths` list options in older ones. Other options, like `lastProjectLocation`, are not
+// projects.
+fn xml_paths(content: &str) -> Vec<String> {
+  let mut paths = xml_children(content, "additionalInfo", "map", "entry", "key");
+  paths.extend(xml_children(
+    content,
+    "recentPaths",
+    "list",
+    "option",
+    "value",
+  ));
+  paths
+}
+
+// The `attribute` of each `child` directly in the `container` of `<option name="{option}">`
+fn xml_children(
+  content: &str,
+  option: &str,
+  container: &str,
+  child: &str,
+  attribute: &str,
+) -> Vec<String> {
+  let Some(start) = content.find(&format!("<option name=\"{}\"", option)) else {
+    return vec![];
+  };
+  // The container has to come right after the option, `<map />` has nothing in it
+  let Some(body) = content[start..]
+    .split_once('>')
+    .and_then(|(_, rest)| rest.trim_start().strip_prefix(&format!("<{}>", container)))
+  else {
+    return vec![];
+  };
+  let mut values = vec![];
+  let mut depth = 0;
+  for (position, _) in body.match_indices('<') {
+    let element = &body[position + 1..];
+    let element = &element[..element.find('>').unwrap_or(element.len())];
+    if element.starts_with('/') {
+      if depth == 0 {
+        // The end of the container
+        break;
+      }
+      depth -= 1;
+      continue;
+    }
+    let name = element.split([' ', '/']).next().unwrap_or_default();
+    if depth == 0 && name == child {
+      let attribute = format!(" {}=\"", attribute);
+      if let Some(value) = element
+        .find(&attribute)
+        .map(|i| &element[i + attribute.len()..])
+      {
+        if let Some(end) = value.find('"') {
+          values.push(xml_unescape(&value[..end]));
+        }
+      }
+    }
+    if !element.ends_with('/') && !element.starts_with(['?', '!']) {
+      depth += 1;
+    }
+  }
+  values
+}
+
+fn xml_unescape(value: &str) -> String {
+  value
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_json() {
+    assert_eq!(
+      parse_json(r#"{"folder": "file:///a\/b", "n": [1, true], "e": "\"\u00e9", "o": {}}"#),
+      Some(Json::Object(vec![
+        (
+          "folder".to_string(),
+          Json::String("file:///a/b".to_string())
+        ),
+        ("n".to_string(), Json::Array(vec![Json::Other, Json::Other])),
+        ("e".to_string(), Json::String("\"é".to_string())),
+        ("o".to_string(), Json::Object(vec![])),
+      ]))
+    );
+    assert_eq!(parse_json(r#"{"unterminated": ["#), None);
+  }
+
+  #[test]
+  fn test_values_at() {
+    let storage = parse_json(
+      r#"{
+  "openedPathsList": {"entries": [{"folderUri": "file:///a"}, {"fileUri": "file:///b.txt"}]},
+  "profileAssociations": {"workspaces": {"file:///c": "__default__profile__"}}
+}"#,
+    )
+    .unwrap();
+    let uris = RECENT_FOLDER_KEYS
+      .iter()
+      .flat_map(|keys| values_at(&storage, keys))
+      .filter_map(Json::as_str)
+      .collect::<Vec<&str>>();
+    // Files and anything else that merely looks like a folder are left out
+    assert_eq!(uris, vec!["file:///a"]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_path_from_file_uri() {
+    assert_eq!(
+      path_from_file_uri("file:///home/me/my%20app"),
+      Some(PathBuf::from("/home/me/my app"))
+    );
+    assert_eq!(
+      path_from_file_uri("file://localhost/srv/app"),
+      Some(PathBuf::from("/srv/app"))
+    );
+    assert_eq!(path_from_file_uri("file://server/share/app"), None);
+    assert_eq!(
+      path_from_file_uri("vscode-remote://ssh-remote+box/app"),
+      None
+    );
+  }
+
+  #[test]
+  fn test_xml_paths() {
+    let content = r#"<application>
+  <component name="RecentProjectsManager">
+    <option name="additionalInfo">
+      <map>
+        <entry key="$USER_HOME$/src/a&amp;b">
+          <value>
+            <RecentProjectMetaInfo frameTitle="a">
+              <option name="build" value="IU-241" />
+            </RecentProjectMetaInfo>
+          </value>
+        </entry>
+      </map>
+    </option>
+    <option name="recentPaths">
+      <list>
+        <option value="/srv/old" />
+      </list>
+    </option>
+    <option name="lastProjectLocation" value="$USER_HOME$/IdeaProjects" />
+  </component>
+</application>"#;
+    assert_eq!(xml_paths(content), vec!["$USER_HOME$/src/a&b", "/srv/old"]);
+  }
+}
@@ -1,9 +1,52 @@
 pub struct PopiFilter {}
 
+// The text typed into the picker, split into the part matched against names and filters
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Query {
+  pub keyword: String,
+  // `type:<name>` words, each one narrows the results to projects of a matching type
+  pub project_types: Vec<String>,
+  // `host:<host>` and `owner:<owner>` words, a repository has to match one of each given
+  pub hosts: Vec<String>,
+  pub owners: Vec<String>,
+  // `is:archived`, only archived repositories are listed
+  pub archived: bool,
+}
+
+impl Query {
+  pub fn parse(text: &str) -> Self {
+    let mut query = Query::default();
+    let mut words: Vec<&str> = vec![];
+    for word in text.split_whitespace() {
+      if word == "is:archived" {
+        query.archived = true;
+        continue;
+      }
+      let filters = match word.split_once(':') {
+        Some(("type", value)) => Some((&mut query.project_types, value)),
+        Some(("host", value)) => Some((&mut query.hosts, value)),
+        Some(("owner", value)) => Some((&mut query.owners, value)),
+        _ => None,
+      };
+      match filters {
+        // Nothing typed after the colon yet, nothing to narrow down by
+        Some((_, "")) => {}
+        Some((filters, value)) => filters.push(value.to_lowercase()),
+        None => words.push(word),
+      }
+    }
+    query.keyword = words.join(" ");
+    query
+  }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct MatchedString {
   pub matched_start: usize,
   pub matched_length: usize,
+  // Byte offsets of the characters the keyword lined up with, within the span above.
+  // Characters the keyword skipped or got wrong are left out.
+  pub matched_positions: Vec<usize>,
   pub distance: usize,
 }
 
@@ -16,7 +59,7 @@ pub enum MatchedResult {
 impl PopiFilter {
   pub fn fuzzy_match(keyword: &str, target: &str) -> MatchedResult {
     let keyword_len = keyword.chars().count();
-    let mut canditates: Vec<MatchedString> = vec![];
+    let mut canditates: Vec<(MatchedString, &str)> = vec![];
 
     let mut keyword_filter: (usize, usize) = (0, 0);
     loop {
@@ -28,7 +71,7 @@ impl PopiFilter {
         ..keyword
           .char_indices()
           .nth(keyword_len - keyword_filter.1)
-          .map_or(keyword_len, |v| v.0)];
+          .map_or(keyword.len(), |v| v.0)];
 
       let keyword_starts_with = clipped_keyword.chars().next().unwrap();
       let keyword_ends_with = clipped_keyword.chars().last().unwrap();
@@ -52,26 +95,34 @@ impl PopiFilter {
             continue;
           }
 
-          canditates.push(Self::get_matched_string(
-            (start_point, end_point),
-            target,
+          canditates.push((
+            Self::get_matched_string(
+              (start_point, end_point),
+              target,
+              clipped_keyword,
+              keyword_filter_penalty,
+            ),
             clipped_keyword,
-            keyword_filter_penalty,
           ));
         }
       }
       keyword_filter = Self::next_start_and_end(keyword_filter);
     }
 
-    if canditates.is_empty() {
-      MatchedResult::NotMatched()
-    } else {
-      MatchedResult::Matched(
-        canditates
+    // Lining up characters takes another pass over the span, only done for the best one
+    match canditates
+      .into_iter()
+      .min_by(|a, b| a.0.distance.cmp(&b.0.distance))
+    {
+      Some((mut matched, clipped_keyword)) => {
+        let span = &target[matched.matched_start..matched.matched_start + matched.matched_length];
+        matched.matched_positions = Self::matched_positions(span, clipped_keyword)
           .into_iter()
-          .min_by(|a, b| a.distance.cmp(&b.distance))
-          .unwrap(),
-      )
+          .map(|position| matched.matched_start + position)
+          .collect();
+        MatchedResult::Matched(matched)
+      }
+      None => MatchedResult::NotMatched(),
     }
   }
 
@@ -82,17 +133,62 @@ impl PopiFilter {
     keyword_filter_penalty: usize,
   ) -> MatchedString {
     let start = *start;
-    let end = *end;
-    let clipped_target = &target[start..end + 1];
+    // Byte offsets, the last matched character may take more than one byte
+    let end = *end + target[*end..].chars().next().map_or(1, char::len_utf8);
+    let clipped_target = &target[start..end];
     MatchedString {
       matched_start: start,
-      matched_length: end - start + 1,
+      matched_length: end - start,
+      matched_positions: vec![],
       distance: usize::try_from(stringmetrics::levenshtein(clipped_target, clipped_keyword))
         .unwrap()
         + keyword_filter_penalty,
     }
   }
 
+  // Byte offsets in target of the characters equal to the keyword's along the cheapest
+  // sequence of edits, the same ones the Levenshtein distance counts
+  pub(super) fn matched_positions(target: &str, keyword: &str) -> Vec<usize> {
+    let target = target.char_indices().collect::<Vec<(usize, char)>>();
+    let keyword = keyword.chars().collect::<Vec<char>>();
+    // distances[i][j]: edits between the first i characters of target and the first j of keyword
+    let mut distances = vec![vec![0; keyword.len() + 1]; target.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+      row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+      *distance = j;
+    }
+    for i in 1..=target.len() {
+      for j in 1..=keyword.len() {
+        let substitution = distances[i - 1][j - 1] + usize::from(target[i - 1].1 != keyword[j - 1]);
+        distances[i][j] = substitution
+          .min(distances[i - 1][j] + 1)
+          .min(distances[i][j - 1] + 1);
+      }
+    }
+
+    // Walk back from the end, taking a matching pair whenever it is on a cheapest path
+    let (mut i, mut j) = (target.len(), keyword.len());
+    let mut positions: Vec<usize> = vec![];
+    while i > 0 && j > 0 {
+      if target[i - 1].1 == keyword[j - 1] && distances[i][j] == distances[i - 1][j - 1] {
+        positions.push(target[i - 1].0);
+        i -= 1;
+        j -= 1;
+      } else if distances[i][j] == distances[i - 1][j] + 1 {
+        i -= 1;
+      } else if distances[i][j] == distances[i][j - 1] + 1 {
+        j -= 1;
+      } else {
+        i -= 1;
+        j -= 1;
+      }
+    }
+    positions.reverse();
+    positions
+  }
+
   pub(super) fn next_start_and_end(current: (usize, usize)) -> (usize, usize) {
     let (start, end) = current;
     if start == 0 {
@@ -103,6 +199,47 @@ impl PopiFilter {
   }
 }
 
+#[cfg(test)]
+mod test_query {
+  use super::*;
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      Query::parse("popi"),
+      Query {
+        keyword: "popi".to_string(),
+        ..Default::default()
+      }
+    );
+    assert_eq!(
+      Query::parse("type:Rust api  work type:"),
+      Query {
+        keyword: "api work".to_string(),
+        project_types: vec!["rust".to_string()],
+        ..Default::default()
+      }
+    );
+    assert_eq!(
+      Query::parse("host:GitHub.com owner:aspulse owner:rust-lang std::fs"),
+      Query {
+        keyword: "std::fs".to_string(),
+        hosts: vec!["github.com".to_string()],
+        owners: vec!["aspulse".to_string(), "rust-lang".to_string()],
+        ..Default::default()
+      }
+    );
+    assert_eq!(
+      Query::parse("is:archived old"),
+      Query {
+        keyword: "old".to_string(),
+        archived: true,
+        ..Default::default()
+      }
+    );
+  }
+}
+
 #[cfg(test)]
 mod test_next_start_and_end {
   use super::*;
@@ -133,6 +270,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 3,
+        matched_positions: vec![0, 1, 2],
         distance: 0,
       })
     );
@@ -141,6 +279,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 2,
+        matched_positions: vec![0, 1],
         distance: 0,
       })
     );
@@ -149,6 +288,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 1,
         matched_length: 2,
+        matched_positions: vec![1, 2],
         distance: 0,
       })
     );
@@ -157,6 +297,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 1,
+        matched_positions: vec![0],
         distance: 0,
       })
     );
@@ -165,6 +306,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 2,
         matched_length: 1,
+        matched_positions: vec![2],
         distance: 0,
       })
     );
@@ -185,6 +327,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 3,
+        matched_positions: vec![0, 2],
         distance: 1,
       })
     );
@@ -193,6 +336,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 5,
+        matched_positions: vec![0, 1, 2, 4],
         distance: 1,
       })
     );
@@ -201,6 +345,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 5,
+        matched_positions: vec![0, 1, 2, 3, 4],
         distance: 1,
       })
     );
@@ -209,6 +354,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 1,
         matched_length: 5,
+        matched_positions: vec![1, 3, 5],
         distance: 2,
       })
     );
@@ -217,6 +363,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 3,
+        matched_positions: vec![0, 1, 2],
         distance: 1,
       })
     );
@@ -229,6 +376,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 1,
         matched_length: 7,
+        matched_positions: vec![1, 2, 3, 4, 6, 7],
         distance: 1,
       })
     );
@@ -237,6 +385,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 7,
+        matched_positions: vec![0, 1, 2, 3, 4, 5, 6],
         distance: 0,
       })
     );
@@ -245,6 +394,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 3,
+        matched_positions: vec![0, 1, 2],
         distance: 0,
       })
     );
@@ -253,6 +403,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 0,
         matched_length: 5,
+        matched_positions: vec![0, 1, 2, 3, 4],
         distance: 0,
       })
     );
@@ -261,6 +412,7 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 6,
         matched_length: 4,
+        matched_positions: vec![6, 7, 8, 9],
         distance: 0,
       })
     );
@@ -269,6 +421,20 @@ mod test_fuzzy_match {
       MatchedResult::Matched(MatchedString {
         matched_start: 3,
         matched_length: 1,
+        matched_positions: vec![3],
+        distance: 0,
+      })
+    );
+  }
+
+  #[test]
+  fn matched_with_multibyte_keyword() {
+    assert_eq!(
+      PopiFilter::fuzzy_match("a › b", "a › b"),
+      MatchedResult::Matched(MatchedString {
+        matched_start: 0,
+        matched_length: 7,
+        matched_positions: vec![0, 1, 2, 5, 6],
         distance: 0,
       })
     );
@@ -1,120 +1,1261 @@
 use async_fs::read_dir;
-use futures::TryStreamExt;
-use std::path::PathBuf;
+use futures::future::join_all;
+use futures::{Stream, TryStreamExt};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore, SemaphorePermit};
 
-use crate::filter::{MatchedResult, MatchedString, PopiFilter};
+use crate::config::{FinderOptions, RepoRoot, RootLayout};
+use crate::filter::{MatchedResult, MatchedString, PopiFilter, Query};
+use crate::git_config::GitConfig;
+use crate::git_info::{repo_config, GitInfo, RepoDetails};
+use crate::project_type::{detect_project_types, ProjectType};
+use crate::source::{DirectorySource, RepoSource, DIRECTORY_SOURCE};
+use crate::workspace::workspace_members;
+
+// An empty file with this name marks a repository as archived
+pub const ARCHIVED_MARKER: &str = ".popi-archived";
+
+// Directories read at the same time across all scans, so that deep scans of many roots
+// do not run out of file descriptors
+const MAX_CONCURRENT_READS: usize = 64;
+static READ_LIMIT: Semaphore = Semaphore::const_new(MAX_CONCURRENT_READS);
 
 pub struct ReposFinder {
-  pub repo_paths: Vec<PathBuf>,
+  pub repo_paths: Vec<RepoRoot>,
+  pub options: FinderOptions,
   pub repos: Option<Vec<Repo>>,
+  // Listed along with the configured roots, see `add_source`
+  sources: Vec<Box<dyn RepoSource>>,
+  // Resolved once per path, symlinks do not change between rescans often enough to matter
+  canonical_paths: HashMap<PathBuf, PathBuf>,
+  // Entries left out of `repos` as they lead to a directory already listed
+  duplicates: Vec<DuplicatePath>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Repo {
   pub path: PathBuf,
-  pub name: String,
+  // Kept as-is from the file system, names are not guaranteed to be UTF-8
+  pub name: OsString,
+  // The configured root this entry was discovered under
+  pub root_path: PathBuf,
+  pub host: Option<String>,
+  pub owner: Option<String>,
+  pub kind: RepoKind,
+  // Filled in lazily once the picker is open, never persisted in the index
+  pub git: Option<GitInfo>,
+  // Unix time in seconds the repository was last worked on, filled in along with `git`
+  pub last_active: Option<i64>,
+  // Detected from marker files like `Cargo.toml` when the repository is discovered
+  pub project_types: Vec<ProjectType>,
+  // Name of the `RepoSource` that listed the entry
+  pub source: String,
+  // Marked with a `.popi-archived` file or `popi.archived` in its git config. Search results
+  // also have it set for paths listed under `archived` in config.yml.
+  pub archived: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RepoKind {
+  #[default]
+  Repository,
+  // A linked worktree (`git worktree add`) of the repository at main_path
+  Worktree {
+    main_path: PathBuf,
+    branch: Option<String>,
+  },
+  // A submodule registered in the `.gitmodules` of the repository at parent_path
+  Submodule {
+    parent_path: PathBuf,
+    parent_name: String,
+    depth: usize,
+  },
+  // A Cargo, npm or pnpm workspace member of the repository at parent_path,
+  // named by its path inside the repository
+  WorkspaceMember {
+    parent_path: PathBuf,
+    parent_name: String,
+  },
+}
+
+impl Repo {
+  pub fn new(path: PathBuf, name: OsString) -> Self {
+    Repo {
+      path,
+      name,
+      ..Default::default()
+    }
+  }
+
+  // The name shown in the list and used for matching, lossy for non UTF-8 names
+  pub fn display_name(&self) -> String {
+    let name = match &self.owner {
+      Some(owner) => format!("{}/{}", owner, self.name.to_string_lossy()),
+      None => self.name.to_string_lossy().into_owned(),
+    };
+    match &self.kind {
+      RepoKind::Worktree {
+        branch: Some(branch),
+        ..
+      } => format!("{}@{}", name, branch),
+      RepoKind::Worktree { branch: None, .. } => format!(
+        "{}@{}",
+        name,
+        self.path.file_name().unwrap_or_default().to_string_lossy()
+      ),
+      RepoKind::Submodule { parent_name, .. } => format!("{}/{}", parent_name, name),
+      RepoKind::WorkspaceMember { parent_name, .. } => format!("{} › {}", parent_name, name),
+      RepoKind::Repository => name,
+    }
+  }
+
+  // Where the repository comes from: its `origin` remote, otherwise the ghq directory layout
+  pub fn origin_host(&self) -> Option<&str> {
+    match self.git.as_ref().and_then(|git| git.origin.as_ref()) {
+      Some(origin) => Some(&origin.host),
+      None => self.host.as_deref(),
+    }
+  }
+
+  pub fn origin_owner(&self) -> Option<&str> {
+    match self.git.as_ref().and_then(|git| git.origin.as_ref()) {
+      Some(origin) => Some(&origin.owner),
+      None => self.owner.as_deref(),
+    }
+  }
+
+  // `github.com/owner` of the origin, unless the directory layout already says the same
+  pub fn origin_location(&self) -> Option<String> {
+    let origin = self.git.as_ref()?.origin.as_ref()?;
+    let same_as_layout =
+      self.host.as_deref() == Some(&origin.host) && self.owner.as_deref() == Some(&origin.owner);
+    (!same_as_layout).then(|| origin.location())
+  }
+
+  // Listed by scanning the configured root at root_path
+  fn scanned_under(&self, root_path: &Path) -> bool {
+    self.source == DIRECTORY_SOURCE && self.root_path == root_path
+  }
+
+  // Found under a dot-prefixed directory like `.cache`, or is one itself
+  pub fn is_hidden(&self) -> bool {
+    // Worktrees can live outside the root, then only their own directory name counts. The
+    // name they share with the main repository says nothing about where they are.
+    let relative = match self.path.strip_prefix(&self.root_path) {
+      Ok(relative) if !self.root_path.as_os_str().is_empty() => relative,
+      _ => self.path.file_name().map(Path::new).unwrap_or(&self.path),
+    };
+    relative
+      .components()
+      .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+  }
+
+  // How far the entry is nested under other entries, used for indentation
+  pub fn depth(&self) -> usize {
+    match &self.kind {
+      RepoKind::Submodule { depth, .. } => *depth,
+      RepoKind::WorkspaceMember { .. } => 1,
+      _ => 0,
+    }
+  }
+
+  pub fn parent_path(&self) -> Option<&Path> {
+    match &self.kind {
+      RepoKind::Submodule { parent_path, .. } | RepoKind::WorkspaceMember { parent_path, .. } => {
+        Some(parent_path)
+      }
+      _ => None,
+    }
+  }
 }
 
 #[derive(Clone, Debug)]
 pub struct FoundRepo {
   pub repo: Repo,
+  // Set when another entry has the same display name, e.g. the root it was found under
+  pub suffix: Option<String>,
+  // Positions refer to `search_text()`
   pub matched_string: MatchedString,
 }
 
+impl FoundRepo {
+  // The display name followed by the disambiguation suffix, if any
+  pub fn search_text(&self) -> String {
+    search_text(&self.repo.display_name(), self.suffix.as_deref())
+  }
+}
+
+fn search_text(display_name: &str, suffix: Option<&str>) -> String {
+  match suffix {
+    Some(suffix) => format!("{} {}", display_name, suffix),
+    None => display_name.to_string(),
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ReposStatus {
   pub paths_not_found: Vec<PathBuf>,
+  pub paths_timed_out: Vec<PathBuf>,
+  pub paths_permission_denied: Vec<PathBuf>,
+  pub io_errors: Vec<ScanError>,
+  pub duplicates: Vec<DuplicatePath>,
+}
+
+// A root or entry leading to a directory already listed under another path, e.g. through
+// a symlink. Only the first one is kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicatePath {
+  pub path: PathBuf,
+  pub same_as: PathBuf,
+}
+
+impl ReposStatus {
+  pub fn record(&mut self, status: &RepoStatus) {
+    match status {
+      RepoStatus::NotFound(path) => self.paths_not_found.push(path.clone()),
+      RepoStatus::TimedOut(path) => self.paths_timed_out.push(path.clone()),
+      RepoStatus::Found(_, errors) => {
+        for error in errors {
+          match error.kind {
+            ScanErrorKind::PermissionDenied => {
+              self.paths_permission_denied.push(error.path.clone())
+            }
+            ScanErrorKind::Io(_) => self.io_errors.push(error.clone()),
+          }
+        }
+      }
+    }
+  }
+}
+
+// What changed under a single root after adding, removing or refreshing it
+#[derive(Debug)]
+pub struct RootDelta {
+  pub root_path: PathBuf,
+  // Entries listed now that were not before
+  pub added: Vec<Repo>,
+  // Entries listed before that are gone now
+  pub removed: Vec<Repo>,
+  pub status: ReposStatus,
+}
+
+// A directory inside a root that could not be listed (completely or partially)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanError {
+  pub path: PathBuf,
+  pub kind: ScanErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanErrorKind {
+  PermissionDenied,
+  Io(String),
+}
+
+impl ScanError {
+  fn new(path: &Path, err: &std::io::Error) -> Self {
+    ScanError {
+      path: path.to_path_buf(),
+      kind: match err.kind() {
+        ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+        _ => ScanErrorKind::Io(err.to_string()),
+      },
+    }
+  }
+}
+
+// Changes to the repository set delivered while main mode is running
+#[derive(Debug)]
+pub enum ReposUpdate {
+  // Every entry discovered under root_path was scanned again
+  RootReplaced {
+    root_path: PathBuf,
+    repos: Vec<Repo>,
+  },
+  // A root finished its first scan, see spawn_scan
+  RootScanned {
+    root_path: PathBuf,
+    status: RepoStatus,
+  },
+  // Every entry of a source other than the roots was listed again, see spawn_sources
+  SourceListed {
+    source: String,
+    repos: Vec<Repo>,
+  },
 }
 
 impl ReposFinder {
-  pub fn new(repo_paths: Vec<PathBuf>) -> Self {
+  pub fn new(repo_paths: Vec<RepoRoot>) -> Self {
+    Self::new_with_options(repo_paths, FinderOptions::default())
+  }
+
+  pub fn new_with_options(repo_paths: Vec<RepoRoot>, options: FinderOptions) -> Self {
     ReposFinder {
       repo_paths,
+      options,
       repos: None,
+      sources: vec![],
+      canonical_paths: HashMap::new(),
+      duplicates: vec![],
     }
   }
 
+  // Entries of the source are merged with the configured roots from the next `init` on
+  pub fn add_source(&mut self, source: impl RepoSource + 'static) {
+    self.sources.push(Box::new(source));
+  }
+
   pub async fn init(&mut self) -> ReposStatus {
     let mut repos: Vec<Repo> = vec![];
-    let mut paths_not_found: Vec<PathBuf> = vec![];
+    let mut status = ReposStatus::default();
 
-    let repos_grep = self.repo_paths.iter().map(|path| {
-      let target_dir = path.clone();
-      tokio::spawn(async move { listup_repos(target_dir).await })
-    });
+    // Each root gets its own task, so one slow root does not hold back the others
+    let mut scans = vec![];
+    for (index, root) in self.repo_paths.clone().into_iter().enumerate() {
+      if let Some(duplicate) = self.duplicate_root(index).await {
+        status.duplicates.push(duplicate);
+        continue;
+      }
+      let source = DirectorySource::new(root, self.options.clone());
+      scans.push(tokio::spawn(async move { source.list().await }));
+    }
+    let mut listed: Vec<(&str, RepoStatus)> = join_all(scans)
+      .await
+      .into_iter()
+      .flatten()
+      .map(|repo_status| (DIRECTORY_SOURCE, repo_status))
+      .collect();
+    let sources = self.sources.iter().map(|source| source.as_ref());
+    listed.extend(
+      join_all(sources.map(|source| async move { (source.name(), source.list().await) })).await,
+    );
+
+    for (name, repo_status) in listed {
+      status.record(&repo_status);
+      if let RepoStatus::Found(found, _) = repo_status {
+        repos.extend(tagged(name, found));
+      }
+    }
+
+    self.set_repos(repos).await;
+    status.duplicates.extend(self.duplicates.iter().cloned());
+    status
+  }
+
+  // Start listing a root, or scan it again with new settings if it is already there
+  pub async fn add_root(&mut self, root: RepoRoot) -> RootDelta {
+    let index = match self
+      .repo_paths
+      .iter()
+      .position(|existing| existing.path == root.path)
+    {
+      Some(index) => {
+        self.repo_paths[index] = root.clone();
+        index
+      }
+      None => {
+        self.repo_paths.push(root.clone());
+        self.repo_paths.len() - 1
+      }
+    };
+    if let Some(duplicate) = self.duplicate_root(index).await {
+      return RootDelta {
+        root_path: root.path,
+        added: vec![],
+        removed: vec![],
+        status: ReposStatus {
+          duplicates: vec![duplicate],
+          ..Default::default()
+        },
+      };
+    }
+    self.rescan(root).await
+  }
+
+  // A root reached through an earlier one's symlink would only list the same entries again
+  async fn duplicate_root(&mut self, index: usize) -> Option<DuplicatePath> {
+    let roots = self.repo_paths[..=index]
+      .iter()
+      .map(|root| root.path.clone())
+      .collect::<Vec<PathBuf>>();
+    self.resolve_paths(roots.iter().cloned()).await;
+    let (path, earlier) = roots.split_last()?;
+    let canonical = self.canonical_path(path);
+    let same_as = earlier
+      .iter()
+      .find(|earlier| self.canonical_path(earlier) == canonical)?;
+    Some(DuplicatePath {
+      path: path.clone(),
+      same_as: same_as.clone(),
+    })
+  }
+
+  // None when no root with this path is configured
+  pub async fn remove_root(&mut self, root_path: &Path) -> Option<RootDelta> {
+    let position = self
+      .repo_paths
+      .iter()
+      .position(|root| root.path == root_path)?;
+    self.repo_paths.remove(position);
+    let (removed, kept): (Vec<Repo>, Vec<Repo>) = self
+      .repos
+      .take()
+      .unwrap_or_default()
+      .into_iter()
+      .partition(|repo| repo.scanned_under(root_path));
+    self.set_repos(kept).await;
+    Some(RootDelta {
+      root_path: root_path.to_path_buf(),
+      added: vec![],
+      removed,
+      status: ReposStatus::default(),
+    })
+  }
+
+  // Scan a configured root again, None when no root with this path is configured
+  pub async fn refresh(&mut self, root_path: &Path) -> Option<RootDelta> {
+    let root = self
+      .repo_paths
+      .iter()
+      .find(|root| root.path == root_path)?
+      .clone();
+    Some(self.rescan(root).await)
+  }
+
+  async fn rescan(&mut self, root: RepoRoot) -> RootDelta {
+    let root_path = root.path.clone();
+    let repo_status = DirectorySource::new(root, self.options.clone())
+      .list()
+      .await;
+    let mut status = ReposStatus::default();
+    status.record(&repo_status);
+
+    let under_root = |repos: &Option<Vec<Repo>>| {
+      repos
+        .iter()
+        .flatten()
+        .filter(|repo| repo.scanned_under(&root_path))
+        .cloned()
+        .collect::<Vec<Repo>>()
+    };
+    let before = under_root(&self.repos);
+    self
+      .apply(ReposUpdate::RootScanned {
+        root_path: root_path.clone(),
+        status: repo_status,
+      })
+      .await;
+    let after = under_root(&self.repos);
+
+    let listed_in = |repos: &[Repo], repo: &Repo| repos.iter().any(|other| other.path == repo.path);
+    RootDelta {
+      added: after
+        .iter()
+        .filter(|repo| !listed_in(&before, repo))
+        .cloned()
+        .collect(),
+      removed: before
+        .into_iter()
+        .filter(|repo| !listed_in(&after, repo))
+        .collect(),
+      root_path,
+      status,
+    }
+  }
 
-    for repo_status in repos_grep {
-      match repo_status.await.unwrap() {
-        RepoStatus::NotFound(path) => paths_not_found.push(path),
-        RepoStatus::Found(repo_int_path) => {
-          repos.extend(repo_int_path);
+  // Replace the repository set, e.g. with entries restored from the on-disk index
+  pub async fn set_repos(&mut self, mut repos: Vec<Repo>) {
+    dedupe_worktrees(&mut repos);
+    self
+      .resolve_paths(
+        repos
+          .iter()
+          .filter(|repo| repo.source == DIRECTORY_SOURCE)
+          .map(|repo| repo.path.clone()),
+      )
+      .await;
+    self.duplicates = self.dedupe_canonical(&mut repos);
+    // A rescan does not change what was already read about a repository
+    if let Some(previous) = &self.repos {
+      let previous = previous
+        .iter()
+        .map(|repo| (&repo.path, repo))
+        .collect::<HashMap<&PathBuf, &Repo>>();
+      for repo in repos.iter_mut() {
+        if let Some(previous) = previous.get(&repo.path) {
+          repo.git = repo.git.take().or_else(|| previous.git.clone());
+          repo.last_active = repo.last_active.or(previous.last_active);
         }
       }
     }
     self.repos = Some(repos);
-    ReposStatus { paths_not_found }
+  }
+
+  // Entries of the scanned roots left out of the list, see `DuplicatePath`
+  pub fn duplicates(&self) -> &[DuplicatePath] {
+    &self.duplicates
+  }
+
+  fn dedupe_canonical(&self, repos: &mut Vec<Repo>) -> Vec<DuplicatePath> {
+    let mut listed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut duplicates: Vec<DuplicatePath> = vec![];
+    repos.retain(|repo| {
+      if repo.source != DIRECTORY_SOURCE {
+        return true;
+      }
+      let canonical = self.canonical_path(&repo.path);
+      match listed.get(&canonical) {
+        Some(same_as) if *same_as != repo.path => {
+          duplicates.push(DuplicatePath {
+            path: repo.path.clone(),
+            same_as: same_as.clone(),
+          });
+          false
+        }
+        Some(_) => true,
+        None => {
+          listed.insert(canonical, repo.path.clone());
+          true
+        }
+      }
+    });
+    duplicates
+  }
+
+  // Resolve the paths not resolved yet, all at once and each within the timeout of its root,
+  // so a hung mount holds up neither startup nor the search
+  async fn resolve_paths(&mut self, paths: impl Iterator<Item = PathBuf>) {
+    let unresolved = paths
+      .filter(|path| !self.canonical_paths.contains_key(path))
+      .collect::<HashSet<PathBuf>>();
+    let resolving = unresolved.into_iter().map(|path| {
+      let timeout = self.timeout_for(&path);
+      async move {
+        let canonical = tokio::time::timeout(timeout, async_fs::canonicalize(&path)).await;
+        (path, canonical)
+      }
+    });
+    for (path, canonical) in join_all(resolving).await {
+      if let Ok(Ok(canonical)) = canonical {
+        self.canonical_paths.insert(path, canonical);
+      }
+    }
+  }
+
+  // Paths that could not be resolved (anymore, or in time) stand for themselves
+  fn canonical_path(&self, path: &Path) -> PathBuf {
+    self
+      .canonical_paths
+      .get(path)
+      .cloned()
+      .unwrap_or_else(|| path.to_path_buf())
+  }
+
+  // The timeout of the most specific root containing the path
+  fn timeout_for(&self, path: &Path) -> Duration {
+    self
+      .repo_paths
+      .iter()
+      .filter(|root| path.starts_with(&root.path))
+      .max_by_key(|root| root.path.components().count())
+      .map_or(self.options.scan_timeout, |root| {
+        root.timeout(&self.options)
+      })
+  }
+
+  pub fn set_details(&mut self, details: Vec<RepoDetails>) {
+    let Some(repos) = &mut self.repos else {
+      return;
+    };
+    let details = details
+      .into_iter()
+      .map(|details| (details.path.clone(), details))
+      .collect::<HashMap<PathBuf, RepoDetails>>();
+    for repo in repos.iter_mut() {
+      if let Some(details) = details.get(&repo.path) {
+        repo.git = details.git.clone();
+        repo.last_active = details.last_active;
+      }
+    }
+  }
+
+  pub async fn apply(&mut self, update: ReposUpdate) {
+    match update {
+      ReposUpdate::RootReplaced { root_path, repos }
+      | ReposUpdate::RootScanned {
+        root_path,
+        status: RepoStatus::Found(repos, _),
+      } => {
+        let mut current = self.repos.take().unwrap_or_default();
+        current.retain(|repo| !repo.scanned_under(&root_path));
+        current.extend(repos);
+        self.set_repos(current).await;
+      }
+      ReposUpdate::RootScanned {
+        root_path,
+        status: RepoStatus::NotFound(_),
+      } => {
+        let mut current = self.repos.take().unwrap_or_default();
+        current.retain(|repo| !repo.scanned_under(&root_path));
+        self.set_repos(current).await;
+      }
+      ReposUpdate::SourceListed { source, repos } => {
+        let mut current = self.repos.take().unwrap_or_default();
+        current.retain(|repo| repo.source != source);
+        current.extend(repos);
+        self.set_repos(current).await;
+      }
+      // Nothing new is known about a root that timed out
+      ReposUpdate::RootScanned {
+        status: RepoStatus::TimedOut(_),
+        ..
+      } => {}
+    }
   }
 
   pub fn listup_repos(&self) -> Vec<Repo> {
     self.repos.clone().unwrap()
   }
 
+  // Hidden entries stay in the list, so toggling only needs another search
+  pub fn set_show_hidden(&mut self, show_hidden: bool) {
+    self.options.show_hidden = show_hidden;
+  }
+
+  pub fn set_show_archived(&mut self, show_archived: bool) {
+    self.options.show_archived = show_archived;
+  }
+
+  fn is_archived(&self, repo: &Repo) -> bool {
+    repo.archived
+      || self
+        .options
+        .archived
+        .iter()
+        .any(|path| repo.path.starts_with(path))
+  }
+
   pub fn search_by(&self, keyword: &str) -> Vec<FoundRepo> {
-    let converted_keyword = convert_to_lower(keyword.to_string());
-    let mut entries = self
-      .repos
-      .clone()
-      .unwrap()
+    let query = Query::parse(keyword);
+    let converted_keyword = convert_to_lower(query.keyword.clone());
+    let show_hidden = self.options.show_hidden;
+    let repos = listed_once(self.repos.clone().unwrap());
+    // Worked out over every entry, so a suffix does not come and go while typing
+    let suffixes = disambiguation_suffixes(&repos);
+    let mut entries = repos
       .into_iter()
-      .map(|repo| {
-        let name = convert_to_lower(repo.name.clone());
-        (repo, PopiFilter::fuzzy_match(&converted_keyword, &name))
+      .zip(suffixes)
+      .filter(|(repo, _)| show_hidden || !repo.is_hidden())
+      .map(|(mut repo, suffix)| {
+        repo.archived = self.is_archived(&repo);
+        (repo, suffix)
       })
-      .filter_map(|(repo, match_result)| match match_result {
-        MatchedResult::Matched(result) => Some((repo, result)),
+      // Archived ones only show up when asked for
+      .filter(|(repo, _)| match query.archived {
+        true => repo.archived,
+        false => !repo.archived || self.options.show_archived,
+      })
+      .filter(|(repo, _)| matches_filters(&query, repo))
+      .map(|(repo, suffix)| {
+        let (name, offsets) =
+          lower_with_offsets(&search_text(&repo.display_name(), suffix.as_deref()));
+        // An empty query lists everything, most recently active first
+        let match_result = if converted_keyword.is_empty() {
+          MatchedResult::Matched(MatchedString {
+            matched_start: 0,
+            matched_length: 0,
+            matched_positions: vec![],
+            distance: 0,
+          })
+        } else {
+          match PopiFilter::fuzzy_match(&converted_keyword, &name) {
+            MatchedResult::Matched(matched) => {
+              MatchedResult::Matched(to_original_offsets(matched, &offsets))
+            }
+            not_matched => not_matched,
+          }
+        };
+        (repo, suffix, match_result)
+      })
+      .filter_map(|(repo, suffix, match_result)| match match_result {
+        MatchedResult::Matched(result) => Some(FoundRepo {
+          repo,
+          suffix,
+          matched_string: result,
+        }),
         MatchedResult::NotMatched() => None,
       })
-      .collect::<Vec<(Repo, MatchedString)>>();
+      .collect::<Vec<FoundRepo>>();
 
-    entries.sort_by(|(_, a), (_, b)| a.distance.partial_cmp(&b.distance).unwrap());
-    entries
-      .into_iter()
-      .map(|(repo, matched_string)| FoundRepo {
-        repo,
-        matched_string,
-      })
-      .collect::<Vec<FoundRepo>>()
+    // Equally good matches are ranked by recent activity, unknown activity last
+    entries.sort_by(|a, b| {
+      a.matched_string
+        .distance
+        .cmp(&b.matched_string.distance)
+        .then_with(|| b.repo.last_active.cmp(&a.repo.last_active))
+    });
+    group_children(entries)
+  }
+}
+
+// Scan every root concurrently, sending each one as soon as it is done,
+// so a slow root does not hold back the others. The channel closes when all roots are done.
+pub fn spawn_scan(
+  roots: Vec<RepoRoot>,
+  options: FinderOptions,
+  reposupdate_tx: mpsc::Sender<ReposUpdate>,
+) {
+  for root in roots {
+    let options = options.clone();
+    let reposupdate_tx = reposupdate_tx.clone();
+    tokio::spawn(async move {
+      let root_path = root.path.clone();
+      let status = scan_root(root, options).await;
+      let _ = reposupdate_tx
+        .send(ReposUpdate::RootScanned { root_path, status })
+        .await;
+    });
   }
 }
 
-enum RepoStatus {
+// List every source concurrently, sending each one's entries as soon as they are ready.
+// Sources not installed or not readable just list nothing.
+pub fn spawn_sources(sources: Vec<Box<dyn RepoSource>>, reposupdate_tx: mpsc::Sender<ReposUpdate>) {
+  for source in sources {
+    let reposupdate_tx = reposupdate_tx.clone();
+    tokio::spawn(async move {
+      let RepoStatus::Found(repos, _) = source.list().await else {
+        return;
+      };
+      let _ = reposupdate_tx
+        .send(ReposUpdate::SourceListed {
+          source: source.name().to_string(),
+          repos: tagged(source.name(), repos),
+        })
+        .await;
+    });
+  }
+}
+
+fn tagged(source: &str, repos: Vec<Repo>) -> Vec<Repo> {
+  repos
+    .into_iter()
+    .map(|mut repo| {
+      repo.source = source.to_string();
+      repo
+    })
+    .collect()
+}
+
+// A directory several sources know about is shown once, preferably as found in a root.
+// Duplicates stay in `repos`, so removing a root brings back the other sources' entries.
+fn listed_once(repos: Vec<Repo>) -> Vec<Repo> {
+  let scanned = repos
+    .iter()
+    .filter(|repo| repo.source == DIRECTORY_SOURCE)
+    .map(|repo| repo.path.clone())
+    .collect::<HashSet<PathBuf>>();
+  let mut seen: HashSet<PathBuf> = HashSet::new();
+  repos
+    .into_iter()
+    .filter(|repo| {
+      repo.source == DIRECTORY_SOURCE
+        || (!scanned.contains(&repo.path) && seen.insert(repo.path.clone()))
+    })
+    .collect()
+}
+
+#[derive(Clone, Debug)]
+pub enum RepoStatus {
   NotFound(PathBuf),
-  Found(Vec<Repo>),
+  // The root did not finish within its timeout, e.g. a hung network mount
+  TimedOut(PathBuf),
+  // Repositories found, along with directories that could not be read
+  Found(Vec<Repo>, Vec<ScanError>),
+}
+
+fn matches_filters(query: &Query, repo: &Repo) -> bool {
+  let starts_with_any = |value: Option<&str>, filters: &[String]| {
+    filters.is_empty()
+      || value.is_some_and(|value| {
+        let value = value.to_lowercase();
+        filters.iter().any(|filter| value.starts_with(filter))
+      })
+  };
+  query.project_types.iter().all(|filter| {
+    repo
+      .project_types
+      .iter()
+      .any(|project_type| project_type.matches(filter))
+  }) && starts_with_any(repo.origin_host(), &query.hosts)
+    && starts_with_any(repo.origin_owner(), &query.owners)
+}
+
+// For every group of entries sharing a display name, the shortest trailing part of their
+// parent directories that tells them apart, e.g. `work` and `personal` for `~/work/api`
+// and `~/personal/api`. Entries with a unique display name get None.
+fn disambiguation_suffixes(repos: &[Repo]) -> Vec<Option<String>> {
+  let display_names = repos
+    .iter()
+    .map(Repo::display_name)
+    .collect::<Vec<String>>();
+  let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+  for (index, display_name) in display_names.iter().enumerate() {
+    groups.entry(display_name).or_default().push(index);
+  }
+
+  let mut suffixes: Vec<Option<String>> = vec![None; repos.len()];
+  for indexes in groups.values().filter(|indexes| indexes.len() > 1) {
+    let parents = indexes
+      .iter()
+      .map(|&index| {
+        let parent = repos[index].path.parent().unwrap_or(Path::new(""));
+        parent.components().rev().collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+    let deepest = parents.iter().map(Vec::len).max().unwrap_or(0);
+    let labels_for = |count: usize| {
+      parents
+        .iter()
+        .map(|components| {
+          let mut taken = components.iter().take(count).rev().collect::<PathBuf>();
+          if taken.as_os_str().is_empty() {
+            taken = PathBuf::from("/");
+          }
+          taken.to_string_lossy().into_owned()
+        })
+        .collect::<Vec<String>>()
+    };
+    // Fall back to the full parent paths when even those are identical
+    let labels = (1..=deepest)
+      .map(labels_for)
+      .find(|labels| {
+        let mut unique = labels.clone();
+        unique.sort();
+        unique.dedup();
+        unique.len() == labels.len()
+      })
+      .unwrap_or_else(|| labels_for(deepest));
+    for (&index, label) in indexes.iter().zip(labels) {
+      suffixes[index] = Some(label);
+    }
+  }
+  suffixes
+}
+
+// Keep children right below their parent when both are found, otherwise keep the order
+fn group_children(found: Vec<FoundRepo>) -> Vec<FoundRepo> {
+  let (mut children, mut result): (Vec<FoundRepo>, Vec<FoundRepo>) = found
+    .into_iter()
+    .partition(|found| found.repo.parent_path().is_some());
+  // Parents are placed before their own children are looked up
+  children.sort_by_key(|child| child.repo.depth());
+  let mut orphans: Vec<FoundRepo> = vec![];
+  let mut placed: Vec<FoundRepo> = vec![];
+  for child in children.into_iter() {
+    let parent_path = child.repo.parent_path().unwrap();
+    let is_placed = |f: &FoundRepo| f.repo.path == parent_path;
+    if result.iter().any(is_placed) || placed.iter().any(is_placed) {
+      placed.push(child);
+    } else {
+      orphans.push(child);
+    }
+  }
+  for child in placed.into_iter() {
+    let parent_path = child.repo.parent_path().unwrap();
+    let index = result
+      .iter()
+      .position(|f| f.repo.path == parent_path)
+      .unwrap();
+    // Skip over siblings already inserted, so the match order is preserved
+    let mut insert_at = index + 1;
+    while insert_at < result.len() && result[insert_at].repo.depth() > result[index].repo.depth() {
+      insert_at += 1;
+    }
+    result.insert(insert_at, child);
+  }
+  // Children whose parent did not match keep their relative order at the end
+  result.extend(orphans);
+  result
+}
+
+// A worktree checked out inside a root is already listed as a worktree entry
+fn dedupe_worktrees(repos: &mut Vec<Repo>) {
+  let worktree_paths = repos
+    .iter()
+    .filter(|repo| matches!(repo.kind, RepoKind::Worktree { .. }))
+    .map(|repo| repo.path.clone())
+    .collect::<Vec<PathBuf>>();
+  repos.retain(|repo| {
+    matches!(repo.kind, RepoKind::Worktree { .. }) || !worktree_paths.contains(&repo.path)
+  });
 }
 
-async fn listup_repos(repo_path: PathBuf) -> RepoStatus {
-  match read_dir(&repo_path).await {
-    Ok(mut repos) => {
+// Scan a single root, giving up after its timeout
+pub(crate) async fn scan_root(root: RepoRoot, options: FinderOptions) -> RepoStatus {
+  let root_path = root.path.clone();
+  let timeout = root.timeout(&options);
+  tokio::time::timeout(timeout, listup_repos(root, options))
+    .await
+    .unwrap_or(RepoStatus::TimedOut(root_path))
+}
+
+async fn listup_repos(root: RepoRoot, options: FinderOptions) -> RepoStatus {
+  let root_path = root.path.clone();
+  match listup_root(root).await {
+    RepoStatus::Found(repos, errors) => {
       let mut result: Vec<Repo> = vec![];
-      while let Some(repo) = {
-        loop {
-          match repos.try_next().await {
-            Ok(repo) => break repo,
-            Err(_) => continue,
+      for repo in repos {
+        let worktrees = listup_worktrees(&repo).await;
+        let submodules = if options.submodules {
+          listup_submodules(&repo).await
+        } else {
+          vec![]
+        };
+        let members = if options.workspaces {
+          listup_workspace_members(&repo).await
+        } else {
+          vec![]
+        };
+        result.push(repo);
+        result.extend(submodules);
+        result.extend(members);
+        result.extend(worktrees);
+      }
+      for repo in result.iter_mut() {
+        repo.root_path = root_path.clone();
+        repo.source = DIRECTORY_SOURCE.to_string();
+        repo.project_types = detect_project_types(&repo.path).await;
+        repo.archived = has_archived_marker(&repo.path).await;
+      }
+      RepoStatus::Found(result, errors)
+    }
+    not_found => not_found,
+  }
+}
+
+async fn listup_root(root: RepoRoot) -> RepoStatus {
+  let mut errors: Vec<ScanError> = vec![];
+  let entries = match read_entries(&root.path, &mut errors).await {
+    Some(entries) => entries,
+    // An unreadable root is reported as an error, only a missing one as not found
+    None if errors.is_empty() => return RepoStatus::NotFound(root.path),
+    None => return RepoStatus::Found(vec![], errors),
+  };
+  match root.layout {
+    RootLayout::Flat => RepoStatus::Found(
+      entries
+        .into_iter()
+        .map(|(path, name)| Repo::new(path, name))
+        .collect(),
+      errors,
+    ),
+    // A host or owner directory symlinked back up the tree would list its parent again
+    RootLayout::Ghq => {
+      let mut result: Vec<Repo> = vec![];
+      let root_id = file_id(&root.path).await;
+      for (host_path, host) in only_dirs(entries).await {
+        let host_id = file_id(&host_path).await;
+        if host_id.is_some() && host_id == root_id {
+          continue;
+        }
+        let owners = read_entries(&host_path, &mut errors)
+          .await
+          .unwrap_or_default();
+        for (owner_path, owner) in only_dirs(owners).await {
+          let owner_id = file_id(&owner_path).await;
+          if owner_id.is_some() && [root_id, host_id].contains(&owner_id) {
+            continue;
+          }
+          let names = read_entries(&owner_path, &mut errors)
+            .await
+            .unwrap_or_default();
+          for (path, name) in only_dirs(names).await {
+            let id = file_id(&path).await;
+            if id.is_some() && [root_id, host_id, owner_id].contains(&id) {
+              continue;
+            }
+            result.push(Repo {
+              host: Some(host.to_string_lossy().into_owned()),
+              owner: Some(owner.to_string_lossy().into_owned()),
+              ..Repo::new(path, name)
+            });
           }
         }
-      } {
-        let path = repo.path();
-        let name = repo.file_name().to_str().unwrap().to_string();
-        result.push(Repo { path, name });
       }
-      RepoStatus::Found(result)
+      RepoStatus::Found(result, errors)
     }
-    Err(_) => RepoStatus::NotFound(repo_path.to_path_buf()),
   }
 }
 
+// Returns None if the directory itself could not be read.
+// Errors are collected into errors, except for the directory simply not existing.
+pub(crate) async fn read_entries(
+  dir: &Path,
+  errors: &mut Vec<ScanError>,
+) -> Option<Vec<(PathBuf, OsString)>> {
+  let _permit = read_permit().await;
+  let entries = match read_dir(dir).await {
+    Ok(entries) => entries,
+    Err(err) => {
+      if !matches!(err.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) {
+        errors.push(ScanError::new(dir, &err));
+      }
+      return None;
+    }
+  };
+  let entries = entries.map_ok(|entry| (entry.path(), entry.file_name()));
+  Some(collect_entries(dir, entries, errors).await)
+}
+
+// Reading stops at the first failing entry, a directory that keeps failing can not loop forever
+async fn collect_entries<T>(
+  dir: &Path,
+  mut entries: impl Stream<Item = std::io::Result<T>> + Unpin,
+  errors: &mut Vec<ScanError>,
+) -> Vec<T> {
+  let mut result: Vec<T> = vec![];
+  loop {
+    match entries.try_next().await {
+      Ok(Some(entry)) => result.push(entry),
+      Ok(None) => break,
+      Err(err) => {
+        errors.push(ScanError::new(dir, &err));
+        break;
+      }
+    }
+  }
+  result
+}
+
+async fn has_archived_marker(path: &Path) -> bool {
+  if is_file(&path.join(ARCHIVED_MARKER)).await {
+    return true;
+  }
+  repo_config(path)
+    .await
+    .and_then(|config| config.get_bool("popi", None, "archived"))
+    == Some(true)
+}
+
+// Held while reading a directory, see MAX_CONCURRENT_READS
+pub(crate) async fn read_permit() -> Option<SemaphorePermit<'static>> {
+  READ_LIMIT.acquire().await.ok()
+}
+
+// `Path::is_dir` and `Path::is_file` without blocking a runtime thread on a hung mount
+pub(crate) async fn is_dir(path: &Path) -> bool {
+  async_fs::metadata(path)
+    .await
+    .is_ok_and(|metadata| metadata.is_dir())
+}
+
+pub(crate) async fn is_file(path: &Path) -> bool {
+  async_fs::metadata(path)
+    .await
+    .is_ok_and(|metadata| metadata.is_file())
+}
+
+// Device and inode number of a directory, following symlinks. A directory showing up again
+// below itself means a symlink cycle.
+#[cfg(unix)]
+pub(crate) async fn file_id(path: &Path) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+  let metadata = async_fs::metadata(path).await.ok()?;
+  Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn file_id(_path: &Path) -> Option<(u64, u64)> {
+  None
+}
+
+// Linked worktrees are registered in `.git/worktrees/<id>/gitdir`,
+// which points to the `.git` file inside the worktree checkout.
+async fn listup_worktrees(repo: &Repo) -> Vec<Repo> {
+  let worktrees_dir = repo.path.join(".git").join("worktrees");
+  let mut result: Vec<Repo> = vec![];
+  // Most repositories have no worktrees at all, nothing worth reporting here
+  let entries = read_entries(&worktrees_dir, &mut vec![])
+    .await
+    .unwrap_or_default();
+  for (admin_dir, _) in only_dirs(entries).await {
+    let Ok(gitdir) = async_fs::read_to_string(admin_dir.join("gitdir")).await else {
+      continue;
+    };
+    let Some(path) = Path::new(gitdir.trim()).parent().map(Path::to_path_buf) else {
+      continue;
+    };
+    if !is_dir(&path).await {
+      continue;
+    }
+    let branch = async_fs::read_to_string(admin_dir.join("HEAD"))
+      .await
+      .ok()
+      .and_then(|head| branch_from_head(&head));
+    result.push(Repo {
+      path,
+      kind: RepoKind::Worktree {
+        main_path: repo.path.clone(),
+        branch,
+      },
+      ..repo.clone()
+    });
+  }
+  result
+}
+
+async fn listup_submodules(repo: &Repo) -> Vec<Repo> {
+  let ancestors = file_id(&repo.path).await.into_iter().collect();
+  listup_submodules_of(repo.path.clone(), repo.display_name(), 1, ancestors).await
+}
+
+// Submodules (and their own submodules) are read from `.gitmodules` in each worktree.
+// Nested submodules are placed right after their parent. A submodule path leading back to
+// one of its ancestors (through a symlink) is skipped.
+fn listup_submodules_of(
+  parent_path: PathBuf,
+  parent_name: String,
+  depth: usize,
+  ancestors: Vec<(u64, u64)>,
+) -> Pin<Box<dyn Future<Output = Vec<Repo>> + Send>> {
+  Box::pin(async move {
+    let Ok(gitmodules) = async_fs::read_to_string(parent_path.join(".gitmodules")).await else {
+      return vec![];
+    };
+    let gitmodules = GitConfig::parse(&gitmodules);
+    let mut result: Vec<Repo> = vec![];
+    for name in gitmodules.subsections("submodule") {
+      let Some(path_in_parent) = gitmodules.get("submodule", Some(name), "path") else {
+        continue;
+      };
+      let path = parent_path.join(path_in_parent);
+      if !is_dir(&path).await {
+        continue;
+      }
+      let id = file_id(&path).await;
+      if id.is_some_and(|id| ancestors.contains(&id)) {
+        continue;
+      }
+      let submodule = Repo {
+        kind: RepoKind::Submodule {
+          parent_path: parent_path.clone(),
+          parent_name: parent_name.clone(),
+          depth,
+        },
+        ..Repo::new(path, OsString::from(path_in_parent))
+      };
+      let mut nested_ancestors = ancestors.clone();
+      nested_ancestors.extend(id);
+      let nested = listup_submodules_of(
+        submodule.path.clone(),
+        submodule.display_name(),
+        depth + 1,
+        nested_ancestors,
+      )
+      .await;
+      result.push(submodule);
+      result.extend(nested);
+    }
+    result
+  })
+}
+
+async fn listup_workspace_members(repo: &Repo) -> Vec<Repo> {
+  let parent_name = repo.display_name();
+  workspace_members(&repo.path)
+    .await
+    .into_iter()
+    .map(|member| Repo {
+      kind: RepoKind::WorkspaceMember {
+        parent_path: repo.path.clone(),
+        parent_name: parent_name.clone(),
+      },
+      ..Repo::new(repo.path.join(&member), OsString::from(member))
+    })
+    .collect()
+}
+
+// `ref: refs/heads/main` becomes `main`, a detached HEAD becomes its short hash
+fn branch_from_head(head: &str) -> Option<String> {
+  let head = head.trim();
+  match head.strip_prefix("ref:") {
+    Some(reference) => {
+      let reference = reference.trim();
+      Some(
+        reference
+          .strip_prefix("refs/heads/")
+          .unwrap_or(reference)
+          .to_string(),
+      )
+    }
+    None if head.len() >= 7 => Some(head[..7].to_string()),
+    None => None,
+  }
+}
+
+pub(crate) async fn only_dirs(entries: Vec<(PathBuf, OsString)>) -> Vec<(PathBuf, OsString)> {
+  let mut dirs = vec![];
+  for (path, name) in entries {
+    if is_dir(&path).await {
+      dirs.push((path, name));
+    }
+  }
+  dirs
+}
+
 fn convert_to_lower(from: String) -> String {
-  from.to_lowercase().replace('_', "-").replace('+', "=")
+  lower_with_offsets(&from).0
+}
+
+// Lowercases like `convert_to_lower` and keeps, for each lowered character, its byte offset
+// paired with the offset it came from in the original name.
+fn lower_with_offsets(from: &str) -> (String, Vec<(usize, usize)>) {
+  let mut lower = String::with_capacity(from.len());
+  let mut offsets: Vec<(usize, usize)> = vec![];
+  for (offset, c) in from.char_indices() {
+    for lower_c in c.to_lowercase() {
+      offsets.push((lower.len(), offset));
+      lower.push(match lower_c {
+        '_' => '-',
+        '+' => '=',
+        lower_c => lower_c,
+      });
+    }
+  }
+  offsets.push((lower.len(), from.len()));
+  (lower, offsets)
+}
+
+// Moves a match found in the text from `lower_with_offsets` back onto the original text
+fn to_original_offsets(matched: MatchedString, offsets: &[(usize, usize)]) -> MatchedString {
+  let original = |lower_offset: usize| {
+    offsets
+      .iter()
+      .find(|(lower, _)| *lower >= lower_offset)
+      .map_or(0, |(_, original)| *original)
+  };
+  let start = original(matched.matched_start);
+  let end = original(matched.matched_start + matched.matched_length);
+  let mut positions = matched
+    .matched_positions
+    .iter()
+    .map(|&position| original(position))
+    .collect::<Vec<usize>>();
+  // Both characters `İ` turns into lead back to it
+  positions.dedup();
+  MatchedString {
+    matched_start: start,
+    matched_length: end - start,
+    matched_positions: positions,
+    distance: matched.distance,
+  }
 }
 
 #[cfg(test)]
@@ -127,4 +1268,49 @@ mod tests {
     assert_eq!(convert_to_lower("aBc_".to_string()), "abc-");
     assert_eq!(convert_to_lower("aBc+".to_string()), "abc=");
   }
+
+  #[test]
+  fn test_lower_with_offsets() {
+    assert_eq!(
+      lower_with_offsets("İ\u{212A}a"),
+      (
+        "i\u{307}ka".to_string(),
+        vec![(0, 0), (1, 0), (3, 2), (4, 5), (5, 6)]
+      )
+    );
+  }
+
+  #[test]
+  fn test_branch_from_head() {
+    assert_eq!(
+      branch_from_head("ref: refs/heads/main\n"),
+      Some("main".to_string())
+    );
+    assert_eq!(
+      branch_from_head("ref: refs/heads/feature/x"),
+      Some("feature/x".to_string())
+    );
+    assert_eq!(
+      branch_from_head("3f2a9c1d0e8b7a6f5e4d3c2b1a09f8e7d6c5b4a3\n"),
+      Some("3f2a9c1".to_string())
+    );
+    assert_eq!(branch_from_head(""), None);
+  }
+
+  #[tokio::test]
+  async fn test_collect_entries() {
+    let dir = Path::new("/repos");
+    let failing = std::io::Error::other("input/output error");
+    // The same error would come back forever, the entry after it is never asked for
+    let entries = futures::stream::iter(vec![Ok("a"), Err(failing), Ok("b")]);
+    let mut errors: Vec<ScanError> = vec![];
+    assert_eq!(collect_entries(dir, entries, &mut errors).await, vec!["a"]);
+    assert_eq!(
+      errors,
+      vec![ScanError {
+        path: dir.to_path_buf(),
+        kind: ScanErrorKind::Io("input/output error".to_string()),
+      }]
+    );
+  }
 }
@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::git_config::GitConfig;
+
+// Everything ghq root detection reads from the outside world.
+// Kept separate from the process environment so it can be pointed at fixtures.
+#[derive(Clone, Debug, Default)]
+pub struct GhqEnvironment {
+  pub ghq_root: Option<OsString>,
+  pub home_dir: Option<PathBuf>,
+  pub xdg_config_home: Option<PathBuf>,
+}
+
+impl GhqEnvironment {
+  pub fn from_process() -> Self {
+    GhqEnvironment {
+      ghq_root: std::env::var_os("GHQ_ROOT").filter(|v| !v.is_empty()),
+      home_dir: dirs::home_dir(),
+      xdg_config_home: std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from),
+    }
+  }
+
+  // Global git config files in the order git reads them
+  fn git_config_files(&self) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![];
+    match (&self.xdg_config_home, &self.home_dir) {
+      (Some(xdg), _) => files.push(xdg.join("git").join("config")),
+      (None, Some(home)) => files.push(home.join(".config").join("git").join("config")),
+      (None, None) => {}
+    }
+    if let Some(home) = &self.home_dir {
+      files.push(home.join(".gitconfig"));
+    }
+    files
+  }
+
+  fn expand_tilde(&self, path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), &self.home_dir) {
+      (Some(rest), Some(home)) => home.join(rest.trim_start_matches(['/', '\\'])),
+      _ => PathBuf::from(path),
+    }
+  }
+}
+
+// Resolve ghq roots the same way ghq does: $GHQ_ROOT first, then every `ghq.root`
+// in the global git config, and finally `~/ghq` when it exists.
+pub fn detect_ghq_roots(env: &GhqEnvironment) -> Vec<PathBuf> {
+  let mut roots: Vec<PathBuf> = match &env.ghq_root {
+    Some(ghq_root) => std::env::split_paths(ghq_root)
+      .filter(|p| !p.as_os_str().is_empty())
+      .map(|p| env.expand_tilde(&p.to_string_lossy()))
+      .collect(),
+    None => {
+      let mut config = GitConfig::default();
+      for file in env.git_config_files() {
+        if let Some(other) = GitConfig::read(&file) {
+          config.merge(other);
+        }
+      }
+      config
+        .get_all("ghq", None, "root")
+        .into_iter()
+        .map(|root| env.expand_tilde(root))
+        .collect()
+    }
+  };
+
+  if roots.is_empty() {
+    if let Some(default_root) = env.home_dir.as_deref().map(default_ghq_root) {
+      if default_root.is_dir() {
+        roots.push(default_root);
+      }
+    }
+  }
+
+  let mut unique: Vec<PathBuf> = vec![];
+  for root in roots {
+    if !unique.contains(&root) {
+      unique.push(root);
+    }
+  }
+  unique
+}
+
+fn default_ghq_root(home: &Path) -> PathBuf {
+  home.join("ghq")
+}
@@ -0,0 +1,181 @@
+use std::path::Path;
+
+// Minimal reader for git's INI-like config format.
+// Only what popi needs: sections, subsections, key/value pairs and quoting.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GitConfig {
+  entries: Vec<GitConfigEntry>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct GitConfigEntry {
+  section: String,
+  subsection: Option<String>,
+  key: String,
+  value: String,
+}
+
+impl GitConfig {
+  pub fn parse(content: &str) -> Self {
+    let mut entries: Vec<GitConfigEntry> = vec![];
+    let mut section = String::new();
+    let mut subsection: Option<String> = None;
+
+    for line in content.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+        continue;
+      }
+      if let Some(header) = line.strip_prefix('[') {
+        let Some(end) = header.rfind(']') else {
+          continue;
+        };
+        let header = &header[..end];
+        match header.split_once(|c: char| c.is_whitespace()) {
+          Some((name, sub)) => {
+            section = name.to_lowercase();
+            subsection = Some(sub.trim().trim_matches('"').to_string());
+          }
+          None => match header.split_once('.') {
+            // Deprecated `[section.subsection]` syntax
+            Some((name, sub)) => {
+              section = name.to_lowercase();
+              subsection = Some(sub.to_string());
+            }
+            None => {
+              section = header.to_lowercase();
+              subsection = None;
+            }
+          },
+        }
+        continue;
+      }
+      let (key, value) = match line.split_once('=') {
+        Some((key, value)) => (key.trim(), parse_value(value)),
+        // A key without value means boolean true
+        None => (line, "true".to_string()),
+      };
+      entries.push(GitConfigEntry {
+        section: section.clone(),
+        subsection: subsection.clone(),
+        key: key.to_lowercase(),
+        value,
+      });
+    }
+    GitConfig { entries }
+  }
+
+  pub fn read(path: &Path) -> Option<Self> {
+    std::fs::read_to_string(path)
+      .ok()
+      .map(|content| Self::parse(&content))
+  }
+
+  // Concatenate another config, its values take priority like git's cascading files
+  pub fn merge(&mut self, other: GitConfig) {
+    self.entries.extend(other.entries);
+  }
+
+  pub fn get_all(&self, section: &str, subsection: Option<&str>, key: &str) -> Vec<&str> {
+    let section = section.to_lowercase();
+    let key = key.to_lowercase();
+    self
+      .entries
+      .iter()
+      .filter(|e| e.section == section && e.subsection.as_deref() == subsection && e.key == key)
+      .map(|e| e.value.as_str())
+      .collect()
+  }
+
+  // The last value wins, same as `git config --get`
+  pub fn get(&self, section: &str, subsection: Option<&str>, key: &str) -> Option<&str> {
+    self.get_all(section, subsection, key).pop()
+  }
+
+  pub fn get_bool(&self, section: &str, subsection: Option<&str>, key: &str) -> Option<bool> {
+    match self.get(section, subsection, key)?.to_lowercase().as_str() {
+      "true" | "yes" | "on" | "1" => Some(true),
+      "false" | "no" | "off" | "0" | "" => Some(false),
+      _ => None,
+    }
+  }
+
+  // Names of all subsections of the given section, e.g. every `[remote "..."]`
+  pub fn subsections(&self, section: &str) -> Vec<&str> {
+    let section = section.to_lowercase();
+    let mut result: Vec<&str> = vec![];
+    for entry in &self.entries {
+      if let Some(subsection) = &entry.subsection {
+        if entry.section == section && !result.contains(&subsection.as_str()) {
+          result.push(subsection);
+        }
+      }
+    }
+    result
+  }
+}
+
+fn parse_value(raw: &str) -> String {
+  let mut value = String::new();
+  let mut in_quote = false;
+  let mut chars = raw.trim().chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' => in_quote = !in_quote,
+      '\\' => match chars.next() {
+        Some('n') => value.push('\n'),
+        Some('t') => value.push('\t'),
+        Some(escaped) => value.push(escaped),
+        None => {}
+      },
+      '#' | ';' if !in_quote => break,
+      _ => value.push(c),
+    }
+  }
+  if in_quote {
+    value
+  } else {
+    value.trim_end().to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_sections_and_values() {
+    let config = GitConfig::parse(
+      r#"
+# comment
+[core]
+  bare = false
+  filemode
+[remote "origin"]
+  url = git@github.com:AsPulse/popi.git ; trailing comment
+  fetch = +refs/heads/*:refs/remotes/origin/*
+[ghq]
+  root = ~/ghq
+  root = "/path/with # hash"
+"#,
+    );
+    assert_eq!(config.get_bool("core", None, "bare"), Some(false));
+    assert_eq!(config.get_bool("core", None, "fileMode"), Some(true));
+    assert_eq!(
+      config.get("remote", Some("origin"), "url"),
+      Some("git@github.com:AsPulse/popi.git")
+    );
+    assert_eq!(
+      config.get_all("ghq", None, "root"),
+      vec!["~/ghq", "/path/with # hash"]
+    );
+    assert_eq!(config.get("ghq", None, "root"), Some("/path/with # hash"));
+    assert_eq!(config.subsections("remote"), vec!["origin"]);
+  }
+
+  #[test]
+  fn parse_legacy_subsection() {
+    let config = GitConfig::parse("[branch.main]\n  remote = origin\n");
+    assert_eq!(config.get("branch", Some("main"), "remote"), Some("origin"));
+  }
+}
@@ -0,0 +1,706 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::finder::is_dir;
+use crate::git_config::GitConfig;
+use crate::git_objects::{parse_hex, Commit, ObjectStore};
+use crate::remote::Remote;
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// How many repositories are read before the results are handed to the picker
+const BATCH_SIZE: usize = 32;
+
+// What `git status` would say about HEAD, read straight from the `.git` directory.
+// Nothing here spawns `git`, so it is cheap enough to run for every repository.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitInfo {
+  // None while HEAD is detached
+  pub branch: Option<String>,
+  // None on a branch without any commit yet
+  pub commit: Option<String>,
+  // Something would be lost by deleting the checkout: the index differs from HEAD's tree
+  // (staged changes), or a tracked file from the index in size or modification time
+  pub dirty: bool,
+  // The `origin` remote from `.git/config`, None for local-only repositories
+  pub origin: Option<Remote>,
+}
+
+// Everything read about a repository after discovery
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepoDetails {
+  pub path: PathBuf,
+  pub git: Option<GitInfo>,
+  // Unix time in seconds, see `last_active`
+  pub last_active: Option<i64>,
+}
+
+impl RepoDetails {
+  pub fn read(repo_path: &Path) -> Self {
+    let repo = GitRepo::open(repo_path);
+    let git = repo.as_ref().and_then(GitInfo::read_from);
+    RepoDetails {
+      path: repo_path.to_path_buf(),
+      last_active: last_active(repo_path, repo.as_ref(), git.as_ref()),
+      git,
+    }
+  }
+}
+
+// Where a repository keeps its state, resolved once and shared by everything read about it,
+// so objects and pack indexes are not looked up again for each read
+pub struct GitRepo {
+  path: PathBuf,
+  git_dir: PathBuf,
+  common_dir: PathBuf,
+  store: ObjectStore,
+}
+
+impl GitRepo {
+  pub fn open(repo_path: &Path) -> Option<Self> {
+    let git_dir = git_dir(repo_path)?;
+    let common_dir = common_dir(&git_dir);
+    Some(GitRepo {
+      path: repo_path.to_path_buf(),
+      store: ObjectStore::open(&common_dir),
+      git_dir,
+      common_dir,
+    })
+  }
+}
+
+impl GitInfo {
+  pub fn read(repo_path: &Path) -> Option<Self> {
+    Self::read_from(&GitRepo::open(repo_path)?)
+  }
+
+  pub fn read_from(repo: &GitRepo) -> Option<Self> {
+    let GitRepo {
+      path: repo_path,
+      git_dir,
+      common_dir,
+      store,
+    } = repo;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let (branch, commit) = match head.strip_prefix("ref:") {
+      Some(reference) => {
+        let reference = reference.trim();
+        let branch = reference
+          .strip_prefix("refs/heads/")
+          .unwrap_or(reference)
+          .to_string();
+        (Some(branch), resolve_ref(common_dir, reference))
+      }
+      None => (None, Some(head.to_string())),
+    };
+    // Staged changes are usually answered by the cache tree alone, checked before stat-ing
+    // every tracked file
+    let dirty = fs::read(git_dir.join("index"))
+      .ok()
+      .and_then(|index| parse_index(&index))
+      .is_some_and(|index| {
+        has_staged_changes(&index, store, commit.as_deref())
+          || index
+            .entries
+            .iter()
+            .filter(|entry| !entry.unchecked)
+            .any(|entry| entry.is_modified(repo_path))
+      });
+    let origin =
+      GitConfig::read(&common_dir.join("config")).and_then(|config| Remote::origin(&config));
+    Some(GitInfo {
+      branch,
+      commit,
+      dirty,
+      origin,
+    })
+  }
+
+  // `main`, or the short hash when detached, followed by `*` when there are changes
+  pub fn label(&self) -> Option<String> {
+    let head = match (&self.branch, &self.commit) {
+      (Some(branch), _) => branch.clone(),
+      (None, Some(commit)) => commit.chars().take(7).collect(),
+      (None, None) => return None,
+    };
+    Some(if self.dirty {
+      format!("{}*", head)
+    } else {
+      head
+    })
+  }
+}
+
+// How far the current branch is from its upstream, and how much is stashed away.
+// Walking commits is slower than everything in GitInfo, main mode reads these one by one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitCounts {
+  // Commits only on the branch and only on its upstream, None without an upstream
+  pub ahead_behind: Option<(usize, usize)>,
+  pub stashes: usize,
+}
+
+impl GitCounts {
+  pub fn read(repo_path: &Path) -> Option<Self> {
+    Some(Self::read_from(&GitRepo::open(repo_path)?))
+  }
+
+  pub fn read_from(repo: &GitRepo) -> Self {
+    let GitRepo {
+      git_dir,
+      common_dir,
+      store,
+      ..
+    } = repo;
+    let stashes = fs::read_to_string(common_dir.join("logs/refs/stash"))
+      .map(|reflog| reflog.lines().filter(|line| !line.is_empty()).count())
+      .unwrap_or(0);
+    let ahead_behind = (|| {
+      let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+      let branch = head
+        .trim()
+        .strip_prefix("ref:")?
+        .trim()
+        .strip_prefix("refs/heads/")?;
+      let config = GitConfig::read(&common_dir.join("config"))?;
+      let upstream = upstream_ref(&config, branch)?;
+      let local = resolve_ref(common_dir, &format!("refs/heads/{}", branch))?;
+      let upstream = resolve_ref(common_dir, &upstream)?;
+      count_ahead_behind(store, &local, &upstream)
+    })();
+    GitCounts {
+      ahead_behind,
+      stashes,
+    }
+  }
+
+  // `↑2 ↓1 ≡3`: commits to push, commits to pull and stash entries, zeros left out
+  pub fn label(&self) -> Option<String> {
+    let (ahead, behind) = self.ahead_behind.unwrap_or_default();
+    let parts = [("↑", ahead), ("↓", behind), ("≡", self.stashes)]
+      .into_iter()
+      .filter(|(_, count)| *count > 0)
+      .map(|(symbol, count)| format!("{}{}", symbol, count))
+      .collect::<Vec<String>>();
+    (!parts.is_empty()).then(|| parts.join(" "))
+  }
+}
+
+// `branch.<name>.remote` and `.merge` point at the ref the branch tracks
+fn upstream_ref(config: &GitConfig, branch: &str) -> Option<String> {
+  let remote = config.get("branch", Some(branch), "remote")?;
+  let merge = config.get("branch", Some(branch), "merge")?;
+  if remote == "." {
+    return Some(merge.to_string());
+  }
+  let merged_branch = merge.strip_prefix("refs/heads/")?;
+  Some(format!("refs/remotes/{}/{}", remote, merged_branch))
+}
+
+// Give up on histories that diverged too far, the counts would not be worth the wait
+const MAX_WALKED_COMMITS: usize = 10_000;
+
+const LOCAL: u8 = 1;
+const UPSTREAM: u8 = 2;
+const COMMON: u8 = LOCAL | UPSTREAM;
+
+// Walk both histories newest first, like `git rev-list --left-right --count local...upstream`.
+// Commits reachable from both sides are common, the walk ends once only those are left.
+fn count_ahead_behind(store: &ObjectStore, local: &str, upstream: &str) -> Option<(usize, usize)> {
+  if local == upstream {
+    return Some((0, 0));
+  }
+  let mut walk = CommitWalk {
+    store,
+    flags: HashMap::new(),
+    commits: HashMap::new(),
+    queue: BinaryHeap::new(),
+  };
+  walk.mark(local, LOCAL)?;
+  walk.mark(upstream, UPSTREAM)?;
+  while walk.queue.iter().any(|(_, id)| walk.flags[id] != COMMON) {
+    let Some((_, id)) = walk.queue.pop() else {
+      break;
+    };
+    let flag = walk.flags[&id];
+    for parent in walk.commits[&id].parents.clone() {
+      walk.mark(&parent, flag)?;
+    }
+  }
+  let ahead = walk.flags.values().filter(|&&flag| flag == LOCAL).count();
+  let behind = walk
+    .flags
+    .values()
+    .filter(|&&flag| flag == UPSTREAM)
+    .count();
+  Some((ahead, behind))
+}
+
+struct CommitWalk<'a> {
+  store: &'a ObjectStore,
+  // Which sides every commit seen so far is reachable from
+  flags: HashMap<String, u8>,
+  commits: HashMap<String, Commit>,
+  // Newest commit first
+  queue: BinaryHeap<(i64, String)>,
+}
+
+impl CommitWalk<'_> {
+  fn mark(&mut self, id: &str, flag: u8) -> Option<()> {
+    let current = self.flags.entry(id.to_string()).or_insert(0);
+    if *current | flag == *current {
+      return Some(());
+    }
+    *current |= flag;
+    if !self.commits.contains_key(id) {
+      if self.commits.len() >= MAX_WALKED_COMMITS {
+        return None;
+      }
+      self
+        .commits
+        .insert(id.to_string(), self.store.read_commit(id)?);
+    }
+    // Queued again when reached from the other side too, so the parents learn about it
+    self.queue.push((self.commits[id].time, id.to_string()));
+    Some(())
+  }
+}
+
+// Read every repository in the background, handing results over in batches
+// so the list fills in gradually on large setups.
+pub fn spawn_reader(
+  paths: Vec<PathBuf>,
+  details_tx: mpsc::Sender<Vec<RepoDetails>>,
+) -> JoinHandle<()> {
+  tokio::task::spawn_blocking(move || {
+    for chunk in paths.chunks(BATCH_SIZE) {
+      let details = chunk
+        .iter()
+        .map(|path| RepoDetails::read(path))
+        .collect::<Vec<RepoDetails>>();
+      if details_tx.blocking_send(details).is_err() {
+        return;
+      }
+    }
+  })
+}
+
+// When the repository was last worked on: the latest HEAD reflog entry, the time of the
+// commit HEAD points at when there is no reflog, and the directory mtime otherwise
+pub fn last_active(repo_path: &Path, repo: Option<&GitRepo>, git: Option<&GitInfo>) -> Option<i64> {
+  let from_git = repo.and_then(|repo| {
+    let reflog = fs::read_to_string(repo.git_dir.join("logs/HEAD")).ok();
+    reflog
+      .as_deref()
+      .and_then(|reflog| reflog.lines().rev().find_map(reflog_time))
+      .or_else(|| {
+        let commit = git?.commit.as_deref()?;
+        Some(repo.store.read_commit(commit)?.time)
+      })
+  });
+  from_git.or_else(|| unix_time(fs::metadata(repo_path).ok()?.modified().ok()?))
+}
+
+// The repository's own `config`, shared by all of its worktrees. Read while scanning, so
+// it resolves `.git` like `git_dir` and `common_dir` but without blocking a runtime thread.
+pub async fn repo_config(repo_path: &Path) -> Option<GitConfig> {
+  let dot_git = repo_path.join(".git");
+  let git_dir = match is_dir(&dot_git).await {
+    true => dot_git,
+    false => {
+      let content = async_fs::read_to_string(&dot_git).await.ok()?;
+      repo_path.join(content.trim().strip_prefix("gitdir:")?.trim())
+    }
+  };
+  let common_dir = match async_fs::read_to_string(git_dir.join("commondir")).await {
+    Ok(common_dir) => git_dir.join(common_dir.trim()),
+    Err(_) => git_dir,
+  };
+  let config = async_fs::read_to_string(common_dir.join("config"))
+    .await
+    .ok()?;
+  Some(GitConfig::parse(&config))
+}
+
+// `.git` is a directory in a regular clone and a `gitdir: <path>` file
+// in linked worktrees and submodules
+fn git_dir(repo_path: &Path) -> Option<PathBuf> {
+  let dot_git = repo_path.join(".git");
+  if dot_git.is_dir() {
+    return Some(dot_git);
+  }
+  let content = fs::read_to_string(&dot_git).ok()?;
+  let gitdir = content.trim().strip_prefix("gitdir:")?.trim();
+  Some(repo_path.join(gitdir))
+}
+
+// Linked worktrees keep refs, logs of branches and objects in the main repository
+fn common_dir(git_dir: &Path) -> PathBuf {
+  match fs::read_to_string(git_dir.join("commondir")) {
+    Ok(common_dir) => git_dir.join(common_dir.trim()),
+    Err(_) => git_dir.to_path_buf(),
+  }
+}
+
+// `<old> <new> Name <email> 1700000000 +0900\tcommit: message`
+fn reflog_time(line: &str) -> Option<i64> {
+  let (_, after_email) = line.split_once('>')?;
+  after_email.split_whitespace().next()?.parse().ok()
+}
+
+fn unix_time(time: SystemTime) -> Option<i64> {
+  i64::try_from(time.duration_since(UNIX_EPOCH).ok()?.as_secs()).ok()
+}
+
+fn resolve_ref(common_dir: &Path, reference: &str) -> Option<String> {
+  match fs::read_to_string(common_dir.join(reference)) {
+    Ok(content) => Some(content.trim().to_string()),
+    Err(_) => {
+      let packed_refs = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+      find_packed_ref(&packed_refs, reference)
+    }
+  }
+}
+
+fn find_packed_ref(packed_refs: &str, reference: &str) -> Option<String> {
+  packed_refs
+    .lines()
+    // `#` starts the header, `^` the peeled commit of the annotated tag above
+    .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+    .filter_map(|line| line.split_once(' '))
+    .find(|(_, name)| name.trim() == reference)
+    .map(|(commit, _)| commit.to_string())
+}
+
+// Whether committing now would record anything, comparing the index with HEAD's tree.
+// Unreadable objects count as no changes, like an unreadable index does.
+fn has_staged_changes(index: &Index, store: &ObjectStore, commit: Option<&str>) -> bool {
+  let Some(commit) = commit else {
+    // Nothing committed yet, everything in the index is staged
+    return !index.entries.is_empty();
+  };
+  let Some(tree) = store.read_commit(commit).map(|commit| commit.tree) else {
+    return false;
+  };
+  // git keeps the tree the index would be committed as until something is staged
+  if let Some(cached) = index.cache_tree {
+    return parse_hex(&tree) != Some(cached);
+  }
+  let Some(files) = store.list_files(&tree) else {
+    return false;
+  };
+  files.len() != index.entries.len()
+    || files.iter().zip(&index.entries).any(|(file, entry)| {
+      path_from_bytes(&file.path) != entry.path || file.mode != entry.mode || file.id != entry.id
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Index {
+  entries: Vec<IndexEntry>,
+  // The root of the cache tree extension, None once something was staged since it was written
+  cache_tree: Option<[u8; 20]>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct IndexEntry {
+  path: PathBuf,
+  mtime: u32,
+  size: u32,
+  mode: u32,
+  id: [u8; 20],
+  // git is told not to look at the work tree copy (assume-unchanged, skip-worktree)
+  unchecked: bool,
+}
+
+impl IndexEntry {
+  fn is_modified(&self, repo_path: &Path) -> bool {
+    // Submodules are directories, their own state is not ours to check
+    if self.mode & 0o170000 == 0o160000 {
+      return false;
+    }
+    let Ok(metadata) = fs::symlink_metadata(repo_path.join(&self.path)) else {
+      return true;
+    };
+    let mtime = metadata
+      .modified()
+      .ok()
+      .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+      .map(|duration| duration.as_secs() as u32);
+    // The index keeps the lower 32 bits only. Entries git could not trust when writing
+    // the index have their size zeroed, those count as changed until git refreshes it.
+    metadata.len() as u32 != self.size || mtime != Some(self.mtime)
+  }
+}
+
+// Tracked paths with the stat data and object names git recorded for them, and the cache
+// tree extension, see gitformat-index(5)
+fn parse_index(index: &[u8]) -> Option<Index> {
+  if index.get(..4)? != b"DIRC" {
+    return None;
+  }
+  let version = read_u32(index, 4)?;
+  if !(2..=4).contains(&version) {
+    return None;
+  }
+  let count = read_u32(index, 8)?;
+  let mut entries: Vec<IndexEntry> = vec![];
+  let mut offset = 12;
+  let mut previous_path: Vec<u8> = vec![];
+  for _ in 0..count {
+    let entry_start = offset;
+    let mtime = read_u32(index, offset + 8)?;
+    let mode = read_u32(index, offset + 24)?;
+    let size = read_u32(index, offset + 36)?;
+    let id = index.get(offset + 40..offset + 60)?.try_into().ok()?;
+    // 40 bytes of stat data and a 20 byte object name come before the flags
+    let flags = u16::from_be_bytes(index.get(offset + 60..offset + 62)?.try_into().ok()?);
+    offset += 62;
+    let mut unchecked = flags & 0x8000 != 0;
+    if flags & 0x4000 != 0 {
+      if version < 3 {
+        return None;
+      }
+      let extended = u16::from_be_bytes(index.get(offset..offset + 2)?.try_into().ok()?);
+      unchecked |= extended & 0x4000 != 0;
+      offset += 2;
+    }
+    let path = if version == 4 {
+      // The path shares a prefix with the previous one, no padding follows
+      let (strip, read) = read_varint(index.get(offset..)?)?;
+      offset += read;
+      let suffix_len = index.get(offset..)?.iter().position(|&b| b == 0)?;
+      let keep = previous_path.len().checked_sub(strip as usize)?;
+      let mut path = previous_path[..keep].to_vec();
+      path.extend_from_slice(&index[offset..offset + suffix_len]);
+      offset += suffix_len + 1;
+      path
+    } else {
+      let path_len = index.get(offset..)?.iter().position(|&b| b == 0)?;
+      let path = index[offset..offset + path_len].to_vec();
+      // Entries are NUL padded to a multiple of eight bytes
+      offset = entry_start + ((offset - entry_start + path_len + 8) & !7);
+      path
+    };
+    entries.push(IndexEntry {
+      path: path_from_bytes(&path),
+      mtime,
+      size,
+      mode,
+      id,
+      unchecked,
+    });
+    previous_path = path;
+  }
+  Some(Index {
+    entries,
+    cache_tree: find_cache_tree(index.get(offset..)?),
+  })
+}
+
+// Extensions follow the entries, the last 20 bytes are the checksum of the whole index.
+// The cache tree starts with the root: an empty path, `<entry count> <subtree count>\n`
+// and the tree's object name, which is left out when the count is -1 (invalidated).
+fn find_cache_tree(extensions: &[u8]) -> Option<[u8; 20]> {
+  let mut offset = 0;
+  while offset + 8 <= extensions.len().saturating_sub(20) {
+    let size = read_u32(extensions, offset + 4)? as usize;
+    let data = extensions.get(offset + 8..offset + 8 + size)?;
+    if &extensions[offset..offset + 4] == b"TREE" {
+      let header = data.strip_prefix(b"\0")?;
+      let header_end = header.iter().position(|&b| b == b'\n')?;
+      let (count, _) = std::str::from_utf8(&header[..header_end])
+        .ok()?
+        .split_once(' ')?;
+      if count.starts_with('-') {
+        return None;
+      }
+      return header.get(header_end + 1..header_end + 21)?.try_into().ok();
+    }
+    offset += 8 + size;
+  }
+  None
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+  use std::os::unix::ffi::OsStrExt;
+  PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+  PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_be_bytes(
+    bytes.get(offset..offset + 4)?.try_into().ok()?,
+  ))
+}
+
+// git's offset encoding, each continuation also adds one
+fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+  let mut read = 0;
+  let mut byte = *bytes.get(read)?;
+  read += 1;
+  let mut value = u64::from(byte & 0x7f);
+  while byte & 0x80 != 0 {
+    byte = *bytes.get(read)?;
+    read += 1;
+    value = ((value + 1) << 7) | u64::from(byte & 0x7f);
+  }
+  Some((value, read))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // `previous` is the path of the entry before, version 4 only stores what differs from it
+  fn index_entry(
+    version: u32,
+    previous: &[u8],
+    path: &[u8],
+    mtime: u32,
+    size: u32,
+    flags: u16,
+  ) -> Vec<u8> {
+    let mut entry = vec![0u8; 8];
+    entry.extend_from_slice(&mtime.to_be_bytes());
+    entry.extend_from_slice(&[0u8; 12]);
+    entry.extend_from_slice(&0o100644u32.to_be_bytes());
+    entry.extend_from_slice(&[0u8; 8]);
+    entry.extend_from_slice(&size.to_be_bytes());
+    entry.extend_from_slice(&[0u8; 20]);
+    entry.extend_from_slice(&(flags | path.len() as u16).to_be_bytes());
+    if version == 4 {
+      let common = previous
+        .iter()
+        .zip(path)
+        .take_while(|(a, b)| a == b)
+        .count();
+      entry.push((previous.len() - common) as u8);
+      entry.extend_from_slice(&path[common..]);
+      entry.push(0);
+    } else {
+      entry.extend_from_slice(path);
+      entry.resize(entry.len() + 8 - entry.len() % 8, 0);
+    }
+    entry
+  }
+
+  fn index(version: u32, entries: &[Vec<u8>]) -> Vec<u8> {
+    let mut index = b"DIRC".to_vec();
+    index.extend_from_slice(&version.to_be_bytes());
+    index.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for entry in entries {
+      index.extend_from_slice(entry);
+    }
+    index
+  }
+
+  #[test]
+  fn test_parse_index() {
+    for version in [2, 4] {
+      let parsed = parse_index(&index(
+        version,
+        &[
+          index_entry(version, b"", b"src/lib.rs", 1700000000, 42, 0),
+          index_entry(version, b"src/lib.rs", b"src/main.rs", 1700000001, 7, 0),
+          index_entry(version, b"src/main.rs", b"vendored", 1700000002, 1, 0x8000),
+        ],
+      ))
+      .unwrap();
+      let entry = |path: &str, mtime, size, unchecked| IndexEntry {
+        path: PathBuf::from(path),
+        mtime,
+        size,
+        mode: 0o100644,
+        id: [0; 20],
+        unchecked,
+      };
+      assert_eq!(
+        parsed,
+        Index {
+          entries: vec![
+            entry("src/lib.rs", 1700000000, 42, false),
+            entry("src/main.rs", 1700000001, 7, false),
+            entry("vendored", 1700000002, 1, true),
+          ],
+          cache_tree: None,
+        }
+      );
+    }
+    assert_eq!(parse_index(b"not an index"), None);
+  }
+
+  #[test]
+  fn test_find_cache_tree() {
+    let extension = |signature: &[u8], data: &[u8]| {
+      let mut extension = signature.to_vec();
+      extension.extend_from_slice(&(data.len() as u32).to_be_bytes());
+      extension.extend_from_slice(data);
+      extension
+    };
+    let checksum = [0u8; 20];
+    let mut valid = b"\x003 1\n".to_vec();
+    valid.extend_from_slice(&[0xab; 20]);
+    valid.extend_from_slice(b"src\x002 0\n");
+    valid.extend_from_slice(&[0xcd; 20]);
+    // Other extensions come first sometimes
+    let extensions = [
+      extension(b"REUC", b"ignored"),
+      extension(b"TREE", &valid),
+      checksum.to_vec(),
+    ]
+    .concat();
+    assert_eq!(find_cache_tree(&extensions), Some([0xab; 20]));
+
+    let invalidated = [extension(b"TREE", b"\x00-1 1\n"), checksum.to_vec()].concat();
+    assert_eq!(find_cache_tree(&invalidated), None);
+    assert_eq!(find_cache_tree(&checksum), None);
+  }
+
+  #[test]
+  fn test_read_varint() {
+    assert_eq!(read_varint(&[0x05]), Some((5, 1)));
+    assert_eq!(read_varint(&[0x80, 0x00]), Some((128, 2)));
+    assert_eq!(read_varint(&[0x80]), None);
+  }
+
+  #[test]
+  fn test_find_packed_ref() {
+    let packed_refs = "# pack-refs with: peeled fully-peeled sorted \n\
+      1111111111111111111111111111111111111111 refs/heads/main\n\
+      2222222222222222222222222222222222222222 refs/tags/v1.0\n\
+      ^3333333333333333333333333333333333333333\n";
+    assert_eq!(
+      find_packed_ref(packed_refs, "refs/heads/main"),
+      Some("1111111111111111111111111111111111111111".to_string())
+    );
+    assert_eq!(find_packed_ref(packed_refs, "refs/heads/feature"), None);
+  }
+
+  #[test]
+  fn test_label() {
+    let info = GitInfo {
+      branch: Some("main".to_string()),
+      commit: None,
+      dirty: true,
+      origin: None,
+    };
+    assert_eq!(info.label(), Some("main*".to_string()));
+    let info = GitInfo {
+      branch: None,
+      commit: Some("0123456789abcdef".to_string()),
+      dirty: false,
+      origin: None,
+    };
+    assert_eq!(info.label(), Some("0123456".to_string()));
+  }
+}
@@ -0,0 +1,471 @@
+use std::cell::OnceCell;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+
+// Objects popi reads are small (commits and trees), anything bigger is most likely not one
+const MAX_OBJECT_SIZE: u64 = 1024 * 1024;
+// Delta chains in real packs stay far below this, a longer one means a broken pack
+const MAX_DELTA_DEPTH: usize = 64;
+
+// Read-only access to loose and packed objects of a repository, enough to walk commits
+// and list what a commit contains.
+// Only SHA-1 repositories with version 2 pack indexes are supported.
+pub struct ObjectStore {
+  objects_dir: PathBuf,
+  // Listed once an object is not found loose, many repositories never get that far
+  packs: OnceCell<Vec<Pack>>,
+}
+
+struct Pack {
+  pack_path: PathBuf,
+  index_path: PathBuf,
+  // Read when the pack is first searched, None if it can not be used
+  index: OnceCell<Option<PackIndex>>,
+}
+
+struct PackIndex {
+  // The whole `.idx` file, it is small compared to the pack itself
+  bytes: Vec<u8>,
+  count: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Commit {
+  pub tree: String,
+  pub parents: Vec<String>,
+  // Committer time in Unix seconds
+  pub time: i64,
+}
+
+impl ObjectStore {
+  // Nothing is read until an object is asked for
+  pub fn open(common_dir: &Path) -> Self {
+    ObjectStore {
+      objects_dir: common_dir.join("objects"),
+      packs: OnceCell::new(),
+    }
+  }
+
+  fn packs(&self) -> &[Pack] {
+    self.packs.get_or_init(|| {
+      fs::read_dir(self.objects_dir.join("pack"))
+        .map(|entries| {
+          entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+            .map(|index_path| Pack {
+              pack_path: index_path.with_extension("pack"),
+              index_path,
+              index: OnceCell::new(),
+            })
+            .collect()
+        })
+        .unwrap_or_default()
+    })
+  }
+
+  pub fn read_commit(&self, id: &str) -> Option<Commit> {
+    let (kind, content) = self.read(id)?;
+    if kind != ObjectKind::Commit {
+      return None;
+    }
+    parse_commit(&content)
+  }
+
+  // Every file below the tree with its path from the top, in the order the index keeps them
+  pub fn list_files(&self, tree_id: &str) -> Option<Vec<TreeFile>> {
+    let mut files: Vec<TreeFile> = vec![];
+    self.collect_files(tree_id, &[], &mut files)?;
+    Some(files)
+  }
+
+  fn collect_files(&self, tree_id: &str, prefix: &[u8], files: &mut Vec<TreeFile>) -> Option<()> {
+    let (kind, content) = self.read(tree_id)?;
+    if kind != ObjectKind::Tree {
+      return None;
+    }
+    for file in parse_tree(&content)? {
+      let mut path = prefix.to_vec();
+      path.extend_from_slice(&file.path);
+      if file.mode == TREE_MODE {
+        path.push(b'/');
+        self.collect_files(&to_hex(&file.id), &path, files)?;
+      } else {
+        files.push(TreeFile { path, ..file });
+      }
+    }
+    Some(())
+  }
+
+  fn read(&self, id: &str) -> Option<(ObjectKind, Vec<u8>)> {
+    let raw_id = parse_hex(id)?;
+    if let Some(object) = self.read_loose(id) {
+      return Some(object);
+    }
+    self
+      .packs()
+      .iter()
+      .find_map(|pack| pack.read(&raw_id, self, 0))
+  }
+
+  fn read_loose(&self, id: &str) -> Option<(ObjectKind, Vec<u8>)> {
+    let file = File::open(self.objects_dir.join(&id[..2]).join(&id[2..])).ok()?;
+    let mut content: Vec<u8> = vec![];
+    ZlibDecoder::new(file)
+      .take(MAX_OBJECT_SIZE)
+      .read_to_end(&mut content)
+      .ok()?;
+    let header_end = content.iter().position(|&b| b == 0)?;
+    let header = std::str::from_utf8(&content[..header_end]).ok()?;
+    let kind = match header.split(' ').next()? {
+      "commit" => ObjectKind::Commit,
+      "tree" => ObjectKind::Tree,
+      "blob" => ObjectKind::Blob,
+      "tag" => ObjectKind::Tag,
+      _ => return None,
+    };
+    Some((kind, content[header_end + 1..].to_vec()))
+  }
+}
+
+// A file, symlink or submodule as recorded in a tree
+#[derive(Debug, PartialEq, Eq)]
+pub struct TreeFile {
+  pub path: Vec<u8>,
+  pub mode: u32,
+  pub id: [u8; 20],
+}
+
+const TREE_MODE: u32 = 0o040000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ObjectKind {
+  Commit,
+  Tree,
+  Blob,
+  Tag,
+}
+
+impl PackIndex {
+  fn read(index_path: &Path) -> Option<Self> {
+    let bytes = fs::read(index_path).ok()?;
+    if bytes.get(..8)? != b"\xfftOc\x00\x00\x00\x02" {
+      return None;
+    }
+    let count = read_u32(&bytes, 8 + 255 * 4)? as usize;
+    // Fan-out, names, CRCs and offsets have to be there at least
+    if bytes.len() < 8 + 256 * 4 + count * 28 {
+      return None;
+    }
+    Some(PackIndex { bytes, count })
+  }
+
+  fn find(&self, raw_id: &[u8; 20]) -> Option<u64> {
+    let names_start = 8 + 256 * 4;
+    let first = raw_id[0] as usize;
+    let mut low = match first {
+      0 => 0,
+      _ => read_u32(&self.bytes, 8 + (first - 1) * 4)? as usize,
+    };
+    let mut high = read_u32(&self.bytes, 8 + first * 4)? as usize;
+    while low < high {
+      let middle = (low + high) / 2;
+      let name = &self.bytes[names_start + middle * 20..names_start + middle * 20 + 20];
+      match name.cmp(&raw_id[..]) {
+        std::cmp::Ordering::Less => low = middle + 1,
+        std::cmp::Ordering::Greater => high = middle,
+        std::cmp::Ordering::Equal => return self.offset_of(middle),
+      }
+    }
+    None
+  }
+
+  fn offset_of(&self, position: usize) -> Option<u64> {
+    let offsets_start = 8 + 256 * 4 + self.count * 24;
+    let offset = read_u32(&self.bytes, offsets_start + position * 4)?;
+    if offset & 0x8000_0000 == 0 {
+      return Some(u64::from(offset));
+    }
+    // Packs over 2GiB keep the real offset in a table of 8 byte entries
+    let large_start = offsets_start + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+    Some(u64::from_be_bytes(
+      self
+        .bytes
+        .get(large_start..large_start + 8)?
+        .try_into()
+        .ok()?,
+    ))
+  }
+}
+
+impl Pack {
+  fn read(
+    &self,
+    raw_id: &[u8; 20],
+    store: &ObjectStore,
+    depth: usize,
+  ) -> Option<(ObjectKind, Vec<u8>)> {
+    let index = self.index.get_or_init(|| PackIndex::read(&self.index_path));
+    let offset = index.as_ref()?.find(raw_id)?;
+    self.read_at(offset, store, depth)
+  }
+
+  fn read_at(
+    &self,
+    offset: u64,
+    store: &ObjectStore,
+    depth: usize,
+  ) -> Option<(ObjectKind, Vec<u8>)> {
+    if depth > MAX_DELTA_DEPTH {
+      return None;
+    }
+    let mut file = File::open(&self.pack_path).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut reader = BufReader::new(file);
+
+    let mut byte = read_byte(&mut reader)?;
+    let kind = (byte >> 4) & 0x7;
+    let mut size = u64::from(byte & 0x0f);
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+      byte = read_byte(&mut reader)?;
+      size |= u64::from(byte & 0x7f) << shift;
+      shift += 7;
+    }
+    if size > MAX_OBJECT_SIZE {
+      return None;
+    }
+
+    let base = match kind {
+      6 => {
+        let mut byte = read_byte(&mut reader)?;
+        let mut distance = u64::from(byte & 0x7f);
+        while byte & 0x80 != 0 {
+          byte = read_byte(&mut reader)?;
+          distance = ((distance + 1) << 7) | u64::from(byte & 0x7f);
+        }
+        Some(self.read_at(offset.checked_sub(distance)?, store, depth + 1)?)
+      }
+      7 => {
+        let mut base_id = [0u8; 20];
+        reader.read_exact(&mut base_id).ok()?;
+        Some(match self.read(&base_id, store, depth + 1) {
+          Some(base) => base,
+          // The base may be in another pack or loose
+          None => store.read(&to_hex(&base_id))?,
+        })
+      }
+      _ => None,
+    };
+
+    let mut content: Vec<u8> = vec![];
+    ZlibDecoder::new(reader)
+      .take(size)
+      .read_to_end(&mut content)
+      .ok()?;
+    match base {
+      Some((base_kind, base_content)) => Some((base_kind, apply_delta(&base_content, &content)?)),
+      None => {
+        let kind = match kind {
+          1 => ObjectKind::Commit,
+          2 => ObjectKind::Tree,
+          3 => ObjectKind::Blob,
+          4 => ObjectKind::Tag,
+          _ => return None,
+        };
+        Some((kind, content))
+      }
+    }
+  }
+}
+
+// A delta is the sizes of base and result, then instructions to copy from the base
+// or insert new bytes, see gitformat-pack(5)
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+  let mut position = 0;
+  let read_size = |position: &mut usize| -> Option<usize> {
+    let mut size = 0usize;
+    let mut shift = 0;
+    loop {
+      let byte = *delta.get(*position)?;
+      *position += 1;
+      size |= usize::from(byte & 0x7f).checked_shl(shift)?;
+      shift += 7;
+      if byte & 0x80 == 0 {
+        return Some(size);
+      }
+    }
+  };
+  let base_size = read_size(&mut position)?;
+  let result_size = read_size(&mut position)?;
+  if base_size != base.len() || result_size as u64 > MAX_OBJECT_SIZE {
+    return None;
+  }
+  let mut result: Vec<u8> = Vec::with_capacity(result_size);
+  while position < delta.len() {
+    let op = delta[position];
+    position += 1;
+    if op & 0x80 != 0 {
+      let mut copy_offset = 0usize;
+      let mut copy_size = 0usize;
+      for bit in 0..4 {
+        if op & (1 << bit) != 0 {
+          copy_offset |= usize::from(*delta.get(position)?) << (bit * 8);
+          position += 1;
+        }
+      }
+      for bit in 0..3 {
+        if op & (1 << (4 + bit)) != 0 {
+          copy_size |= usize::from(*delta.get(position)?) << (bit * 8);
+          position += 1;
+        }
+      }
+      if copy_size == 0 {
+        copy_size = 0x10000;
+      }
+      result.extend_from_slice(base.get(copy_offset..copy_offset.checked_add(copy_size)?)?);
+    } else if op != 0 {
+      let insert_size = usize::from(op);
+      result.extend_from_slice(delta.get(position..position + insert_size)?);
+      position += insert_size;
+    } else {
+      return None;
+    }
+  }
+  (result.len() == result_size).then_some(result)
+}
+
+fn parse_commit(content: &[u8]) -> Option<Commit> {
+  let content = String::from_utf8_lossy(content);
+  let mut tree: Option<String> = None;
+  let mut parents: Vec<String> = vec![];
+  let mut time: Option<i64> = None;
+  for line in content.lines().take_while(|line| !line.is_empty()) {
+    if let Some(id) = line.strip_prefix("tree ") {
+      tree = Some(id.trim().to_string());
+    } else if let Some(parent) = line.strip_prefix("parent ") {
+      parents.push(parent.trim().to_string());
+    } else if let Some(committer) = line.strip_prefix("committer ") {
+      let (_, after_email) = committer.rsplit_once('>')?;
+      time = after_email.split_whitespace().next()?.parse().ok();
+    }
+  }
+  Some(Commit {
+    tree: tree?,
+    parents,
+    time: time?,
+  })
+}
+
+// `<octal mode> <name>\0<20 byte object name>` for each entry, see gitformat-tree(5)
+fn parse_tree(content: &[u8]) -> Option<Vec<TreeFile>> {
+  let mut files: Vec<TreeFile> = vec![];
+  let mut rest = content;
+  while !rest.is_empty() {
+    let space = rest.iter().position(|&b| b == b' ')?;
+    let mode = u32::from_str_radix(std::str::from_utf8(&rest[..space]).ok()?, 8).ok()?;
+    let name_end = space + 1 + rest[space + 1..].iter().position(|&b| b == 0)?;
+    let id = rest.get(name_end + 1..name_end + 21)?.try_into().ok()?;
+    files.push(TreeFile {
+      path: rest[space + 1..name_end].to_vec(),
+      mode,
+      id,
+    });
+    rest = &rest[name_end + 21..];
+  }
+  Some(files)
+}
+
+pub(crate) fn parse_hex(id: &str) -> Option<[u8; 20]> {
+  if id.len() != 40 || !id.is_ascii() {
+    return None;
+  }
+  let mut raw = [0u8; 20];
+  for (index, byte) in raw.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&id[index * 2..index * 2 + 2], 16).ok()?;
+  }
+  Some(raw)
+}
+
+fn to_hex(raw_id: &[u8; 20]) -> String {
+  raw_id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_byte(reader: &mut impl Read) -> Option<u8> {
+  let mut byte = [0u8; 1];
+  reader.read_exact(&mut byte).ok()?;
+  Some(byte[0])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_be_bytes(
+    bytes.get(offset..offset + 4)?.try_into().ok()?,
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_apply_delta() {
+    let base = b"hello world";
+    // Sizes 11 and 16, copy 6 bytes from offset 0, insert "there", copy 5 bytes from offset 6
+    let mut delta = vec![11, 16, 0x90, 6, 5];
+    delta.extend_from_slice(b"there");
+    delta.extend_from_slice(&[0x91, 6, 5]);
+    assert_eq!(
+      apply_delta(base, &delta),
+      Some(b"hello thereworld".to_vec())
+    );
+    assert_eq!(apply_delta(base, &[12, 1, 1, b'x']), None);
+  }
+
+  #[test]
+  fn test_parse_commit() {
+    let commit = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+      parent 1111111111111111111111111111111111111111\n\
+      parent 2222222222222222222222222222222222222222\n\
+      author A <a@example.com> 1600000000 +0000\n\
+      committer A <a@example.com> 1650000000 +0900\n\nparent in the message\n";
+    assert_eq!(
+      parse_commit(commit),
+      Some(Commit {
+        tree: "4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string(),
+        parents: vec![
+          "1111111111111111111111111111111111111111".to_string(),
+          "2222222222222222222222222222222222222222".to_string(),
+        ],
+        time: 1650000000,
+      })
+    );
+  }
+
+  #[test]
+  fn test_parse_tree() {
+    let mut tree = b"100644 Cargo.toml\0".to_vec();
+    tree.extend_from_slice(&[0x11; 20]);
+    tree.extend_from_slice(b"40000 src\0");
+    tree.extend_from_slice(&[0x22; 20]);
+    assert_eq!(
+      parse_tree(&tree),
+      Some(vec![
+        TreeFile {
+          path: b"Cargo.toml".to_vec(),
+          mode: 0o100644,
+          id: [0x11; 20],
+        },
+        TreeFile {
+          path: b"src".to_vec(),
+          mode: TREE_MODE,
+          id: [0x22; 20],
+        },
+      ])
+    );
+    assert_eq!(parse_tree(b"100644 truncated\0\x11"), None);
+  }
+}
@@ -0,0 +1,637 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use futures::future::join_all;
+use tokio::sync::mpsc;
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter, YamlLoader};
+
+use crate::config::{FinderOptions, RepoRoot, RootLayout};
+use crate::finder::{
+  is_dir, read_entries, scan_root, Repo, RepoKind, RepoStatus, ReposStatus, ReposUpdate, ScanError,
+  ScanErrorKind,
+};
+use crate::project_type::ProjectType;
+use crate::source::DIRECTORY_SOURCE;
+
+pub const INDEX_FILE_NAME: &str = "index.yml";
+const INDEX_VERSION: i64 = 4;
+
+// Files declaring workspace members, see `workspace::workspace_members`
+const WORKSPACE_MANIFESTS: &[&str] = &["Cargo.toml", "package.json", "pnpm-workspace.yaml"];
+
+// Discovered repositories persisted under LocalStorage.root_path,
+// so the picker can open without scanning every root first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepoIndex {
+  pub options: FinderOptions,
+  pub roots: Vec<IndexedRoot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedRoot {
+  pub root: RepoRoot,
+  // Modification time in milliseconds of every path the scan depends on, -1 if missing
+  pub mtimes: Vec<(PathBuf, i64)>,
+  pub repos: Vec<Repo>,
+  // Directories the scan could not read, reported again while the index is still fresh
+  pub errors: Vec<ScanError>,
+}
+
+// Outcome of `RepoIndex::repos_for`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CachedRepos {
+  pub repos: Vec<Repo>,
+  // Roots the index has nothing for (not found or timed out last time, or newly configured),
+  // they need a full scan
+  pub uncovered: Vec<RepoRoot>,
+}
+
+// Outcome of `RepoIndex::revalidate`
+#[derive(Debug)]
+pub struct Revalidation {
+  // The updated index, None if everything is still up to date
+  pub index: Option<RepoIndex>,
+  // One per root, in order. Unchanged roots report what their last scan found.
+  pub statuses: Vec<(PathBuf, RepoStatus)>,
+}
+
+impl RepoIndex {
+  // Fingerprint freshly scanned repos. Roots that were not found or timed out are left out,
+  // so the next launch scans them again instead of trusting an empty list.
+  pub async fn build(
+    roots: &[RepoRoot],
+    options: &FinderOptions,
+    repos: &[Repo],
+    status: &ReposStatus,
+  ) -> Self {
+    let mut indexed: Vec<IndexedRoot> = vec![];
+    for root in roots {
+      if status.paths_timed_out.contains(&root.path) {
+        continue;
+      }
+      let repos = repos
+        .iter()
+        .filter(|repo| repo.root_path == root.path)
+        .cloned()
+        .collect::<Vec<Repo>>();
+      let errors = status
+        .paths_permission_denied
+        .iter()
+        .map(|path| ScanError {
+          path: path.clone(),
+          kind: ScanErrorKind::PermissionDenied,
+        })
+        .chain(status.io_errors.iter().cloned())
+        .filter(|error| error.path.starts_with(&root.path))
+        .collect();
+      if let Some(mtimes) = fingerprint_within(root, options, &repos).await {
+        indexed.push(IndexedRoot {
+          root: root.clone(),
+          mtimes,
+          repos,
+          errors,
+        });
+      }
+    }
+    RepoIndex {
+      options: options.clone(),
+      roots: indexed,
+    }
+  }
+
+  pub fn load(root_path: &Path) -> Option<Self> {
+    let content = std::fs::read_to_string(root_path.join(INDEX_FILE_NAME)).ok()?;
+    let payload = YamlLoader::load_from_str(&content).ok()?;
+    from_yaml(payload.first()?)
+  }
+
+  pub fn save(&self, root_path: &Path) -> std::io::Result<()> {
+    let mut content = String::new();
+    YamlEmitter::new(&mut content)
+      .dump(&to_yaml(self))
+      .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Could not emit index"))?;
+    std::fs::create_dir_all(root_path)?;
+    // Write then rename, so a crash never leaves a truncated index behind
+    let temp_path = root_path.join(format!("{}.tmp", INDEX_FILE_NAME));
+    std::fs::write(&temp_path, content)?;
+    std::fs::rename(temp_path, root_path.join(INDEX_FILE_NAME))
+  }
+
+  // Cached repos for the given roots, None if the options changed what a scan finds
+  pub fn repos_for(&self, roots: &[RepoRoot], options: &FinderOptions) -> Option<CachedRepos> {
+    if !same_results(&self.options, options) {
+      return None;
+    }
+    let mut cached = CachedRepos::default();
+    for root in roots {
+      match self
+        .roots
+        .iter()
+        .find(|indexed| same_root(&indexed.root, root))
+      {
+        Some(indexed) => cached.repos.extend(indexed.repos.iter().cloned()),
+        None => cached.uncovered.push(root.clone()),
+      }
+    }
+    Some(cached)
+  }
+
+  // Rescan only the roots whose fingerprint changed. Roots are checked concurrently and, given
+  // a sender, each one's status is sent as soon as it is known.
+  pub async fn revalidate(
+    &self,
+    roots: &[RepoRoot],
+    options: &FinderOptions,
+    reposupdate_tx: Option<mpsc::Sender<ReposUpdate>>,
+  ) -> Revalidation {
+    let same_options = same_results(&self.options, options);
+    let checks = roots.iter().map(|root| {
+      let cached = self
+        .roots
+        .iter()
+        .find(|indexed| same_options && same_root(&indexed.root, root))
+        .cloned();
+      let root = root.clone();
+      let options = options.clone();
+      let reposupdate_tx = reposupdate_tx.clone();
+      tokio::spawn(async move {
+        let check = revalidate_root(root, cached, &options).await;
+        if let Some(reposupdate_tx) = reposupdate_tx {
+          let _ = reposupdate_tx
+            .send(ReposUpdate::RootScanned {
+              root_path: check.root_path.clone(),
+              status: check.status.clone(),
+            })
+            .await;
+        }
+        check
+      })
+    });
+    let mut changed = !same_options || self.roots.len() != roots.len();
+    let mut indexed: Vec<IndexedRoot> = vec![];
+    let mut statuses: Vec<(PathBuf, RepoStatus)> = vec![];
+    for check in join_all(checks).await.into_iter().flatten() {
+      changed |= check.changed;
+      indexed.extend(check.indexed);
+      statuses.push((check.root_path, check.status));
+    }
+    Revalidation {
+      index: changed.then(|| RepoIndex {
+        options: options.clone(),
+        roots: indexed,
+      }),
+      statuses,
+    }
+  }
+
+  pub fn repos(&self) -> Vec<Repo> {
+    self
+      .roots
+      .iter()
+      .flat_map(|indexed| indexed.repos.iter().cloned())
+      .collect()
+  }
+}
+
+// Only options changing which repositories are found invalidate the index
+fn same_results(a: &FinderOptions, b: &FinderOptions) -> bool {
+  a.submodules == b.submodules && a.workspaces == b.workspaces
+}
+
+// What revalidating a single root came to
+struct RootCheck {
+  root_path: PathBuf,
+  // The entry to keep in the index, if any
+  indexed: Option<IndexedRoot>,
+  status: RepoStatus,
+  changed: bool,
+}
+
+async fn revalidate_root(
+  root: RepoRoot,
+  cached: Option<IndexedRoot>,
+  options: &FinderOptions,
+) -> RootCheck {
+  if let Some(cached) = &cached {
+    if fingerprint_within(&root, options, &cached.repos)
+      .await
+      .as_ref()
+      == Some(&cached.mtimes)
+    {
+      return RootCheck {
+        root_path: root.path.clone(),
+        status: RepoStatus::Found(cached.repos.clone(), cached.errors.clone()),
+        indexed: Some(IndexedRoot {
+          root,
+          ..cached.clone()
+        }),
+        changed: false,
+      };
+    }
+  }
+  let status = scan_root(root.clone(), options.clone()).await;
+  let (indexed, changed) = match &status {
+    RepoStatus::Found(repos, errors) => {
+      let indexed = fingerprint_within(&root, options, repos)
+        .await
+        .map(|mtimes| IndexedRoot {
+          root: root.clone(),
+          mtimes,
+          repos: repos.clone(),
+          errors: errors.clone(),
+        });
+      (indexed, true)
+    }
+    RepoStatus::NotFound(_) => (None, true),
+    // Keep what we had, a slow mount is not a reason to forget its repositories
+    RepoStatus::TimedOut(_) => (cached, false),
+  };
+  RootCheck {
+    root_path: root.path,
+    indexed,
+    status,
+    changed,
+  }
+}
+
+fn same_root(a: &RepoRoot, b: &RepoRoot) -> bool {
+  a.path == b.path && a.layout == b.layout
+}
+
+async fn fingerprint_within(
+  root: &RepoRoot,
+  options: &FinderOptions,
+  repos: &[Repo],
+) -> Option<Vec<(PathBuf, i64)>> {
+  tokio::time::timeout(root.timeout(options), fingerprint(root, options, repos))
+    .await
+    .ok()
+    .flatten()
+}
+
+// Directories whose listing the scan depends on, plus the places worktrees, submodules and
+// workspace members are registered, so that adding any of them is noticed without reading
+// every directory again.
+async fn fingerprint(
+  root: &RepoRoot,
+  options: &FinderOptions,
+  repos: &[Repo],
+) -> Option<Vec<(PathBuf, i64)>> {
+  let mut paths: Vec<PathBuf> = vec![root.path.clone()];
+  if root.layout == RootLayout::Ghq {
+    for (host_path, _) in read_entries(&root.path, &mut vec![]).await? {
+      if !is_dir(&host_path).await {
+        continue;
+      }
+      paths.push(host_path.clone());
+      for (owner_path, _) in read_entries(&host_path, &mut vec![])
+        .await
+        .unwrap_or_default()
+      {
+        if is_dir(&owner_path).await {
+          paths.push(owner_path);
+        }
+      }
+    }
+  }
+  for repo in repos {
+    if repo.kind == RepoKind::Repository {
+      // Marker files for the project types are right inside the repository
+      paths.push(repo.path.clone());
+      paths.push(repo.path.join(".git").join("worktrees"));
+      paths.push(repo.path.join(".gitmodules"));
+      // Where `popi.archived` is set
+      paths.push(repo.path.join(".git").join("config"));
+      if options.workspaces {
+        for manifest in WORKSPACE_MANIFESTS {
+          paths.push(repo.path.join(manifest));
+        }
+      }
+    }
+    // A new member next to an existing one changes the directory they share
+    if let RepoKind::WorkspaceMember { .. } = repo.kind {
+      if let Some(parent) = repo.path.parent() {
+        if !paths.iter().any(|path| path == parent) {
+          paths.push(parent.to_path_buf());
+        }
+      }
+    }
+  }
+
+  let mut mtimes: Vec<(PathBuf, i64)> = vec![];
+  for path in paths {
+    let mtime = modified_millis(&path).await;
+    if path == root.path && mtime.is_none() {
+      return None;
+    }
+    mtimes.push((path, mtime.unwrap_or(-1)));
+  }
+  Some(mtimes)
+}
+
+async fn modified_millis(path: &Path) -> Option<i64> {
+  let modified = async_fs::metadata(path).await.ok()?.modified().ok()?;
+  let millis = modified.duration_since(UNIX_EPOCH).ok()?.as_millis();
+  i64::try_from(millis).ok()
+}
+
+fn path_to_yaml(path: &Path) -> Yaml {
+  os_str_to_yaml(path.as_os_str())
+}
+
+fn path_from_yaml(value: &Yaml) -> Option<PathBuf> {
+  os_string_from_yaml(value).map(PathBuf::from)
+}
+
+// UTF-8 values are stored as plain strings, anything else as `{ bytes: <hex> }`
+// so a non UTF-8 directory name survives the round trip unchanged.
+fn os_str_to_yaml(value: &OsStr) -> Yaml {
+  match value.to_str() {
+    Some(value) => Yaml::String(value.to_string()),
+    None => {
+      let hex = os_str_bytes(value)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+      let mut hash = Hash::new();
+      insert(&mut hash, "bytes", Yaml::String(hex));
+      Yaml::Hash(hash)
+    }
+  }
+}
+
+fn os_string_from_yaml(value: &Yaml) -> Option<OsString> {
+  if let Some(value) = value.as_str() {
+    return Some(OsString::from(value));
+  }
+  let hex = value["bytes"].as_str()?;
+  if hex.len() % 2 != 0 || !hex.is_ascii() {
+    return None;
+  }
+  let bytes = (0..hex.len())
+    .step_by(2)
+    .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).ok())
+    .collect::<Option<Vec<u8>>>()?;
+  os_string_from_bytes(bytes)
+}
+
+#[cfg(unix)]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+  use std::os::unix::ffi::OsStrExt;
+  value.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+  value.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+  use std::os::unix::ffi::OsStringExt;
+  Some(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+  String::from_utf8(bytes).ok().map(OsString::from)
+}
+
+fn insert(hash: &mut Hash, key: &str, value: Yaml) {
+  hash.insert(Yaml::String(key.to_string()), value);
+}
+
+fn to_yaml(index: &RepoIndex) -> Yaml {
+  let mut options = Hash::new();
+  insert(
+    &mut options,
+    "submodules",
+    Yaml::Boolean(index.options.submodules),
+  );
+  insert(
+    &mut options,
+    "workspaces",
+    Yaml::Boolean(index.options.workspaces),
+  );
+
+  let roots = index
+    .roots
+    .iter()
+    .map(|indexed| {
+      let mut root = Hash::new();
+      insert(&mut root, "path", path_to_yaml(&indexed.root.path));
+      insert(
+        &mut root,
+        "layout",
+        Yaml::String(
+          match indexed.root.layout {
+            RootLayout::Flat => "flat",
+            RootLayout::Ghq => "ghq",
+          }
+          .to_string(),
+        ),
+      );
+      let mtimes = indexed
+        .mtimes
+        .iter()
+        .map(|(path, mtime)| {
+          let mut entry = Hash::new();
+          insert(&mut entry, "path", path_to_yaml(path));
+          insert(&mut entry, "mtime", Yaml::Integer(*mtime));
+          Yaml::Hash(entry)
+        })
+        .collect();
+      insert(&mut root, "mtimes", Yaml::Array(mtimes));
+      insert(
+        &mut root,
+        "repos",
+        Yaml::Array(indexed.repos.iter().map(repo_to_yaml).collect()),
+      );
+      insert(
+        &mut root,
+        "errors",
+        Yaml::Array(indexed.errors.iter().map(error_to_yaml).collect()),
+      );
+      Yaml::Hash(root)
+    })
+    .collect();
+
+  let mut payload = Hash::new();
+  insert(&mut payload, "version", Yaml::Integer(INDEX_VERSION));
+  insert(&mut payload, "options", Yaml::Hash(options));
+  insert(&mut payload, "roots", Yaml::Array(roots));
+  Yaml::Hash(payload)
+}
+
+fn repo_to_yaml(repo: &Repo) -> Yaml {
+  let mut entry = Hash::new();
+  insert(&mut entry, "path", path_to_yaml(&repo.path));
+  insert(&mut entry, "name", os_str_to_yaml(&repo.name));
+  insert(&mut entry, "root_path", path_to_yaml(&repo.root_path));
+  if let Some(host) = &repo.host {
+    insert(&mut entry, "host", Yaml::String(host.clone()));
+  }
+  if let Some(owner) = &repo.owner {
+    insert(&mut entry, "owner", Yaml::String(owner.clone()));
+  }
+  if !repo.project_types.is_empty() {
+    let project_types = repo
+      .project_types
+      .iter()
+      .map(|project_type| Yaml::String(project_type.name().to_string()))
+      .collect();
+    insert(&mut entry, "project_types", Yaml::Array(project_types));
+  }
+  if repo.archived {
+    insert(&mut entry, "archived", Yaml::Boolean(true));
+  }
+  match &repo.kind {
+    RepoKind::Repository => {
+      insert(&mut entry, "kind", Yaml::String("repository".to_string()));
+    }
+    RepoKind::Worktree { main_path, branch } => {
+      insert(&mut entry, "kind", Yaml::String("worktree".to_string()));
+      insert(&mut entry, "main_path", path_to_yaml(main_path));
+      if let Some(branch) = branch {
+        insert(&mut entry, "branch", Yaml::String(branch.clone()));
+      }
+    }
+    RepoKind::Submodule {
+      parent_path,
+      parent_name,
+      depth,
+    } => {
+      insert(&mut entry, "kind", Yaml::String("submodule".to_string()));
+      insert(&mut entry, "parent_path", path_to_yaml(parent_path));
+      insert(&mut entry, "parent_name", Yaml::String(parent_name.clone()));
+      insert(&mut entry, "depth", Yaml::Integer(*depth as i64));
+    }
+    RepoKind::WorkspaceMember {
+      parent_path,
+      parent_name,
+    } => {
+      insert(&mut entry, "kind", Yaml::String("member".to_string()));
+      insert(&mut entry, "parent_path", path_to_yaml(parent_path));
+      insert(&mut entry, "parent_name", Yaml::String(parent_name.clone()));
+    }
+  }
+  Yaml::Hash(entry)
+}
+
+fn error_to_yaml(error: &ScanError) -> Yaml {
+  let mut entry = Hash::new();
+  insert(&mut entry, "path", path_to_yaml(&error.path));
+  match &error.kind {
+    ScanErrorKind::PermissionDenied => {
+      insert(
+        &mut entry,
+        "kind",
+        Yaml::String("permission_denied".to_string()),
+      );
+    }
+    ScanErrorKind::Io(message) => {
+      insert(&mut entry, "kind", Yaml::String("io".to_string()));
+      insert(&mut entry, "message", Yaml::String(message.clone()));
+    }
+  }
+  Yaml::Hash(entry)
+}
+
+fn error_from_yaml(entry: &Yaml) -> Option<ScanError> {
+  let kind = match entry["kind"].as_str()? {
+    "permission_denied" => ScanErrorKind::PermissionDenied,
+    "io" => ScanErrorKind::Io(entry["message"].as_str()?.to_string()),
+    _ => return None,
+  };
+  Some(ScanError {
+    path: path_from_yaml(&entry["path"])?,
+    kind,
+  })
+}
+
+fn from_yaml(payload: &Yaml) -> Option<RepoIndex> {
+  if payload["version"].as_i64()? != INDEX_VERSION {
+    return None;
+  }
+  let options = FinderOptions {
+    submodules: payload["options"]["submodules"].as_bool()?,
+    workspaces: payload["options"]["workspaces"].as_bool()?,
+    ..Default::default()
+  };
+  let roots = payload["roots"]
+    .as_vec()?
+    .iter()
+    .map(|root| {
+      let layout = match root["layout"].as_str()? {
+        "flat" => RootLayout::Flat,
+        "ghq" => RootLayout::Ghq,
+        _ => return None,
+      };
+      let mtimes = root["mtimes"]
+        .as_vec()?
+        .iter()
+        .map(|entry| Some((path_from_yaml(&entry["path"])?, entry["mtime"].as_i64()?)))
+        .collect::<Option<Vec<(PathBuf, i64)>>>()?;
+      let repos = root["repos"]
+        .as_vec()?
+        .iter()
+        .map(repo_from_yaml)
+        .collect::<Option<Vec<Repo>>>()?;
+      let errors = root["errors"]
+        .as_vec()?
+        .iter()
+        .map(error_from_yaml)
+        .collect::<Option<Vec<ScanError>>>()?;
+      Some(IndexedRoot {
+        root: RepoRoot::new(path_from_yaml(&root["path"])?, layout),
+        mtimes,
+        repos,
+        errors,
+      })
+    })
+    .collect::<Option<Vec<IndexedRoot>>>()?;
+  Some(RepoIndex { options, roots })
+}
+
+fn repo_from_yaml(entry: &Yaml) -> Option<Repo> {
+  let kind = match entry["kind"].as_str()? {
+    "repository" => RepoKind::Repository,
+    "worktree" => RepoKind::Worktree {
+      main_path: path_from_yaml(&entry["main_path"])?,
+      branch: entry["branch"].as_str().map(str::to_string),
+    },
+    "submodule" => RepoKind::Submodule {
+      parent_path: path_from_yaml(&entry["parent_path"])?,
+      parent_name: entry["parent_name"].as_str()?.to_string(),
+      depth: usize::try_from(entry["depth"].as_i64()?).ok()?,
+    },
+    "member" => RepoKind::WorkspaceMember {
+      parent_path: path_from_yaml(&entry["parent_path"])?,
+      parent_name: entry["parent_name"].as_str()?.to_string(),
+    },
+    _ => return None,
+  };
+  Some(Repo {
+    path: path_from_yaml(&entry["path"])?,
+    name: os_string_from_yaml(&entry["name"])?,
+    root_path: path_from_yaml(&entry["root_path"])?,
+    host: entry["host"].as_str().map(str::to_string),
+    owner: entry["owner"].as_str().map(str::to_string),
+    kind,
+    // Only scanned roots are kept in the index
+    source: DIRECTORY_SOURCE.to_string(),
+    archived: entry["archived"].as_bool().unwrap_or(false),
+    // Types this version does not know about are dropped
+    project_types: entry["project_types"]
+      .as_vec()
+      .map(|project_types| {
+        project_types
+          .iter()
+          .filter_map(|name| ProjectType::from_name(name.as_str()?))
+          .collect()
+      })
+      .unwrap_or_default(),
+    ..Default::default()
+  })
+}
@@ -1,23 +1,55 @@
+pub mod cli;
 pub mod colors;
 pub mod config;
+pub mod editors;
 pub mod filter;
 pub mod finder;
+pub mod ghq;
+pub mod git_config;
+pub mod git_info;
+pub mod git_objects;
+pub mod index;
 pub mod main_mode;
+pub mod project_type;
+pub mod remote;
+pub mod source;
+pub mod stale;
 pub mod strings;
 pub mod terminal_util;
+pub mod watcher;
+pub mod workspace;
 
 use colored::Colorize;
 
-use crate::config::{LoadConfigError, LocalStorage};
-use crate::finder::ReposFinder;
-use crate::main_mode::call_main_mode;
-use crate::strings::{ERROR_PREFIX, POPI_HEADER, WARNING_PREFIX};
-use crate::terminal_util::VERTICAL_LINE;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+use crate::cli::{CliArgs, Command};
+use crate::config::{FinderOptions, LoadConfigError, LocalStorage, RepoRoot};
+use crate::editors::{EditorEnvironment, JetBrainsSource, VsCodeSource};
+use crate::finder::{
+  spawn_scan, spawn_sources, Repo, RepoStatus, ReposFinder, ReposStatus, ReposUpdate,
+};
+use crate::index::RepoIndex;
+use crate::main_mode::{call_main_mode, ScanProgress};
+use crate::strings::{ERROR_PREFIX, POPI_HEADER};
+use crate::watcher::spawn_watcher;
 
 #[tokio::main]
 pub async fn run() {
   colored::control::set_override(true);
-  startup_message();
+  let cli_args = CliArgs::from_env().unwrap_or_else(|err| {
+    eprintln!(
+      " {} {}",
+      ERROR_PREFIX.on_red().white().bold(),
+      err.to_string().red()
+    );
+    std::process::exit(2);
+  });
+  let json_output = matches!(&cli_args.command, Command::Stale(args) if args.json);
+  if !json_output {
+    startup_message();
+  }
 
   let storage = LocalStorage::new().unwrap_or_else(|err| {
     match err {
@@ -25,11 +57,12 @@ pub async fn run() {
         let mut config_yaml_path = root_path.clone();
         config_yaml_path.push("config.yml");
         eprintln!(
-          " {} {}\n\n Run following commands to edit:\n {}\n {}",
+          " {} {}\n\n Run following commands to edit:\n {}\n {}\n\n {}",
           ERROR_PREFIX.on_red().white().bold(),
           "config.yml not found in your config directory.".red(),
-          format!("$ mkdir -p \"{}\"", root_path.to_str().unwrap()).bold(),
-          format!("$ vim \"{}\"", config_yaml_path.to_str().unwrap()).bold(),
+          format!("$ mkdir -p \"{}\"", root_path.to_string_lossy()).bold(),
+          format!("$ vim \"{}\"", config_yaml_path.to_string_lossy()).bold(),
+          "ghq users can also set ghq.root in ~/.gitconfig or $GHQ_ROOT instead.".bright_black(),
         );
       }
       LoadConfigError::ConfigInvalidYamlFormat { config_yml_path } => {
@@ -43,39 +76,153 @@ pub async fn run() {
     std::process::exit(1);
   });
 
-  eprintln!(" {}", "Loading Repositories...".bright_black());
-  let mut finder = ReposFinder::new(storage.repo_paths.to_vec());
-  let repos_status = finder.init().await;
-  eprintln!(" {}\n", "Finished!".bright_black());
+  if let Command::Stale(args) = &cli_args.command {
+    stale::run(&storage, args).await;
+    return;
+  }
+
+  let mut finder =
+    ReposFinder::new_with_options(storage.repo_paths.to_vec(), storage.finder_options.clone());
+  let (reposupdate_tx, reposupdate_rx) = mpsc::channel::<ReposUpdate>(20);
+
+  let cached = if cli_args.rebuild_index {
+    None
+  } else {
+    RepoIndex::load(&storage.root_path).and_then(|index| {
+      index
+        .repos_for(&storage.repo_paths, &storage.finder_options)
+        .map(|cached| (index, cached))
+    })
+  };
+
+  let scan_progress = match cached {
+    Some((index, cached)) => {
+      finder.set_repos(cached.repos).await;
+      tokio::spawn(revalidate_index(
+        index,
+        storage.repo_paths.to_vec(),
+        cached.uncovered,
+        storage.finder_options.clone(),
+        storage.root_path.clone(),
+        reposupdate_tx.clone(),
+      ));
+      // Revalidation reports every root again, cached ones included
+      ScanProgress::new(root_paths(&storage.repo_paths))
+    }
+    None => {
+      finder.set_repos(vec![]).await;
+      tokio::spawn(scan_and_index(
+        storage.repo_paths.to_vec(),
+        storage.finder_options.clone(),
+        storage.root_path.clone(),
+        reposupdate_tx.clone(),
+      ));
+      ScanProgress::new(root_paths(&storage.repo_paths))
+    }
+  };
+
+  if storage.finder_options.recent_projects {
+    let env = EditorEnvironment::from_process();
+    spawn_sources(
+      vec![
+        Box::new(VsCodeSource { env: env.clone() }),
+        Box::new(JetBrainsSource { env }),
+      ],
+      reposupdate_tx.clone(),
+    );
+  }
 
-  if !repos_status.paths_not_found.is_empty() {
+  if spawn_watcher(
+    storage.repo_paths.to_vec(),
+    storage.finder_options.clone(),
+    reposupdate_tx,
+  )
+  .is_err()
+  {
     eprintln!(
-      " {} Following paths are not found:",
-      WARNING_PREFIX.on_yellow().black().bold(),
+      " {}\n",
+      "Could not watch your roots, new repositories will show up on the next launch."
+        .bright_black()
     );
-    for path in repos_status.paths_not_found {
-      eprintln!(
-        " {} - {}",
-        VERTICAL_LINE.yellow(),
-        path.to_str().unwrap_or("(Unknown Path)")
-      );
-    }
-    eprintln!(" {}", VERTICAL_LINE.yellow());
+  }
 
-    let warning_skip = !terminal_util::yes_or_no(format!(
-      " {} {}",
-      VERTICAL_LINE.yellow(),
-      "Do you want to continue?".bold()
-    ));
-
-    if warning_skip {
-      std::process::exit(130);
-    } else {
-      eprintln!();
+  call_main_mode(storage, finder, reposupdate_rx, scan_progress).await;
+}
+
+// Forward every root to the picker as soon as it is scanned, then write the index
+async fn scan_and_index(
+  repo_paths: Vec<RepoRoot>,
+  finder_options: FinderOptions,
+  root_path: PathBuf,
+  reposupdate_tx: mpsc::Sender<ReposUpdate>,
+) {
+  let index = scan_to_index(repo_paths, &finder_options, &reposupdate_tx).await;
+  let _ = index.save(&root_path);
+}
+
+// The picker is already running on the cached index, push fresh results and the status of
+// each root as soon as it is known. Roots the index does not cover are scanned meanwhile.
+async fn revalidate_index(
+  index: RepoIndex,
+  repo_paths: Vec<RepoRoot>,
+  uncovered: Vec<RepoRoot>,
+  finder_options: FinderOptions,
+  root_path: PathBuf,
+  reposupdate_tx: mpsc::Sender<ReposUpdate>,
+) {
+  let covered = repo_paths
+    .iter()
+    .filter(|root| !uncovered.contains(root))
+    .cloned()
+    .collect::<Vec<RepoRoot>>();
+  let scanning = !uncovered.is_empty();
+  let (revalidation, scanned) = tokio::join!(
+    index.revalidate(&covered, &finder_options, Some(reposupdate_tx.clone())),
+    scan_to_index(uncovered, &finder_options, &reposupdate_tx),
+  );
+  if revalidation.index.is_none() && !scanning {
+    return;
+  }
+  let mut index = revalidation.index.unwrap_or(index);
+  index.roots.extend(scanned.roots);
+  // Keep the configured order, for the next launch to list roots the same way
+  index.roots.sort_by_key(|indexed| {
+    repo_paths
+      .iter()
+      .position(|root| root.path == indexed.root.path)
+  });
+  let _ = index.save(&root_path);
+}
+
+// Scan roots from scratch, forwarding each one as soon as it is done, and index the results
+async fn scan_to_index(
+  repo_paths: Vec<RepoRoot>,
+  finder_options: &FinderOptions,
+  reposupdate_tx: &mpsc::Sender<ReposUpdate>,
+) -> RepoIndex {
+  let (scan_tx, mut scan_rx) = mpsc::channel::<ReposUpdate>(20);
+  spawn_scan(repo_paths.to_vec(), finder_options.clone(), scan_tx);
+
+  let mut repos: Vec<Repo> = vec![];
+  let mut status = ReposStatus::default();
+  while let Some(update) = scan_rx.recv().await {
+    if let ReposUpdate::RootScanned {
+      status: root_status,
+      ..
+    } = &update
+    {
+      if let RepoStatus::Found(found, _) = root_status {
+        repos.extend(found.iter().cloned());
+      }
+      status.record(root_status);
     }
+    let _ = reposupdate_tx.send(update).await;
   }
+  RepoIndex::build(&repo_paths, finder_options, &repos, &status).await
+}
 
-  call_main_mode(storage, finder).await;
+fn root_paths(roots: &[RepoRoot]) -> Vec<PathBuf> {
+  roots.iter().map(|root| root.path.clone()).collect()
 }
 
 fn startup_message() {
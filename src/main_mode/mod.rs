@@ -1,18 +1,22 @@
 mod safe_methods;
 mod split_by_matched;
+mod worker_git_counts;
 mod worker_key_input;
 mod worker_keyword_change;
+mod worker_spinner;
 
 use crate::{
   colors::{BACKGROUND_PINK_COLOR, LIGHTER_PINK_COLOR, PINK_COLOR},
   config::LocalStorage,
-  finder::{FoundRepo, Repo, ReposFinder},
+  finder::{FoundRepo, Repo, RepoStatus, ReposFinder, ReposStatus, ReposUpdate},
+  git_info::{GitCounts, GitInfo},
+  source::DIRECTORY_SOURCE,
   strings::{
     CLEAR_MESSAGE, CLEAR_MESSAGE_LEN, ERROR_PREFIX, EXIT_MESSAGE, EXIT_MESSAGE_LEN, POPI_HEADER,
   },
   terminal_util::{
-    BOTTOM_LEFT_CORNER, BOTTOM_RIGHT_CORNER, HORIZONTAL_LINE, TOP_LEFT_CORNER, TOP_RIGHT_CORNER,
-    VERTICAL_LINE,
+    print_path, BOTTOM_LEFT_CORNER, BOTTOM_RIGHT_CORNER, HORIZONTAL_LINE, SPINNER_FRAMES,
+    TOP_LEFT_CORNER, TOP_RIGHT_CORNER, VERTICAL_LINE,
   },
 };
 use colored::Colorize;
@@ -23,19 +27,29 @@ use crossterm::{
 };
 use crossterm::{queue, style, terminal};
 use std::{
-  cmp, env,
+  cmp,
+  collections::HashMap,
+  env,
   io::{stderr, Write},
+  path::{Path, PathBuf},
   sync::Arc,
 };
 use thiserror::Error;
 use tokio::sync::{mpsc, RwLock, RwLockWriteGuard};
 
 use safe_methods::{safe_move_to, safe_repeat};
-use split_by_matched::split_by_matched;
+use split_by_matched::{split_at_suffix, split_by_matched};
+use worker_git_counts::git_counts;
 use worker_key_input::key_input;
 use worker_keyword_change::keyword_change;
-
-pub async fn call_main_mode(_storage: LocalStorage, finder: ReposFinder) {
+use worker_spinner::spinner;
+
+pub async fn call_main_mode(
+  _storage: LocalStorage,
+  finder: ReposFinder,
+  reposupdate_rx: mpsc::Receiver<ReposUpdate>,
+  scan_progress: ScanProgress,
+) {
   let mut stderr = stderr();
   execute!(
     stderr,
@@ -45,7 +59,7 @@ pub async fn call_main_mode(_storage: LocalStorage, finder: ReposFinder) {
   .unwrap();
 
   enable_raw_mode().unwrap();
-  let main_mode_process = main_mode(finder).await;
+  let main_mode_process = main_mode(finder, reposupdate_rx, scan_progress).await;
   disable_raw_mode().unwrap();
 
   execute!(
@@ -60,15 +74,17 @@ pub async fn call_main_mode(_storage: LocalStorage, finder: ReposFinder) {
 
   match main_mode_process {
     Ok(Some(repo)) => {
-      let path = repo.path.to_str().unwrap().bold();
-      env::set_var("POPI_REPO_PATH", repo.path.to_str().unwrap());
+      let path = repo.path.to_string_lossy().bold();
+      env::set_var("POPI_REPO_PATH", &repo.path);
       eprintln!(" {} {}", "Go ahead!".cyan().bold(), path.normal());
       eprintln!(
         " {}",
         "Path to repository was written to stdout.".bright_black(),
       );
       eprintln!();
-      println!("{}", repo.path.to_str().unwrap());
+      if print_path(&repo.path).is_err() {
+        std::process::exit(1);
+      }
       std::process::exit(0);
     }
     Ok(None) => {
@@ -103,6 +119,80 @@ pub enum MainModeError {
   WorkerJoinError,
 }
 
+// How many roots are still being scanned while main mode is already running
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+  pub total: usize,
+  // Roots not scanned yet, in the configured order
+  pub pending: Vec<PathBuf>,
+  // What the roots scanned so far reported, with the duplicates known to the finder
+  pub status: ReposStatus,
+}
+
+impl ScanProgress {
+  pub fn new(root_paths: Vec<PathBuf>) -> Self {
+    ScanProgress {
+      total: root_paths.len(),
+      pending: root_paths,
+      ..Default::default()
+    }
+  }
+
+  pub fn finish(&mut self, root_path: &Path, status: &RepoStatus) {
+    self.pending.retain(|path| path != root_path);
+    self.status.record(status);
+  }
+
+  pub fn finished(&self) -> usize {
+    self.total - self.pending.len()
+  }
+
+  pub fn is_scanning(&self) -> bool {
+    !self.pending.is_empty()
+  }
+
+  // Problems are only highlighted once scanning is over, the spinner takes the line until then
+  pub fn has_problems(&self) -> bool {
+    let problems = problem_paths(&self.status);
+    !self.is_scanning() && problems.iter().any(|(paths, ..)| !paths.is_empty())
+  }
+}
+
+// The paths behind each kind of problem, with how the status line words them
+pub(crate) fn problem_paths(status: &ReposStatus) -> [(Vec<&Path>, &'static str, &'static str); 5] {
+  [
+    (as_paths(&status.paths_not_found), "root", "not found"),
+    (as_paths(&status.paths_timed_out), "root", "timed out"),
+    (
+      as_paths(&status.paths_permission_denied),
+      "path",
+      "permission denied",
+    ),
+    (
+      status
+        .io_errors
+        .iter()
+        .map(|error| error.path.as_path())
+        .collect(),
+      "path",
+      "unreadable",
+    ),
+    (
+      status
+        .duplicates
+        .iter()
+        .map(|duplicate| duplicate.path.as_path())
+        .collect(),
+      "path",
+      "listed twice",
+    ),
+  ]
+}
+
+fn as_paths(paths: &[PathBuf]) -> Vec<&Path> {
+  paths.iter().map(PathBuf::as_path).collect()
+}
+
 pub(super) enum EscapeBehavior {
   Clear,
   Exit,
@@ -114,13 +204,21 @@ pub(super) struct RenderContext {
   repos: Vec<FoundRepo>,
   repo_selected_index: usize,
   cursor_show: bool,
+  scan_progress: ScanProgress,
+  spinner_frame: usize,
+  // Filled in by the git counts worker, keyed by repository path
+  git_counts: HashMap<PathBuf, GitCounts>,
+  // Whether entries under dot-prefixed directories are listed, toggled with Ctrl+T
+  show_hidden: bool,
+  // Whether archived repositories are listed with the others, toggled with Ctrl+A
+  show_archived: bool,
 }
 
 #[derive(Debug)]
 pub(super) enum ContextChange {
   RenderContextChanged,
   KeywordChanged,
-  Finished(Result<Option<Repo>, MainModeError>),
+  Finished(Result<Option<Box<Repo>>, MainModeError>),
 }
 
 #[derive(Clone)]
@@ -129,7 +227,11 @@ struct MainModeWorker {
   contextchange_tx: mpsc::Sender<ContextChange>,
 }
 
-async fn main_mode(finder: ReposFinder) -> Result<Option<Repo>, MainModeError> {
+async fn main_mode(
+  finder: ReposFinder,
+  reposupdate_rx: mpsc::Receiver<ReposUpdate>,
+  scan_progress: ScanProgress,
+) -> Result<Option<Repo>, MainModeError> {
   let (contextchange_tx, mut contextchange_rx) = mpsc::channel::<ContextChange>(20);
   let (keywordchange_tx, keywordchange_rx) = mpsc::channel::<String>(20);
 
@@ -139,6 +241,11 @@ async fn main_mode(finder: ReposFinder) -> Result<Option<Repo>, MainModeError> {
     repos: vec![],
     repo_selected_index: 0,
     cursor_show: true,
+    scan_progress,
+    spinner_frame: 0,
+    git_counts: HashMap::new(),
+    show_hidden: finder.options.show_hidden,
+    show_archived: finder.options.show_archived,
   }));
 
   let worker = MainModeWorker {
@@ -146,9 +253,15 @@ async fn main_mode(finder: ReposFinder) -> Result<Option<Repo>, MainModeError> {
     contextchange_tx,
   };
 
-  let keyword_change_worker =
-    tokio::spawn(keyword_change(finder, keywordchange_rx, worker.clone()));
+  let keyword_change_worker = tokio::spawn(keyword_change(
+    finder,
+    keywordchange_rx,
+    reposupdate_rx,
+    worker.clone(),
+  ));
   let key_input_worker = tokio::spawn(key_input(worker.clone()));
+  let spinner_worker = tokio::spawn(spinner(worker.clone()));
+  let git_counts_worker = tokio::spawn(git_counts(worker.clone()));
 
   worker
     .contextchange_tx
@@ -171,17 +284,65 @@ async fn main_mode(finder: ReposFinder) -> Result<Option<Repo>, MainModeError> {
         keywordchange_tx.send(keyword).await.unwrap();
       }
       ContextChange::Finished(result) => {
-        break result;
+        break result.map(|repo| repo.map(|repo| *repo));
       }
     }
   };
   contextchange_rx.close();
-  tokio::join!(keyword_change_worker, key_input_worker,)
-    .0
+  let (keyword_change_result, key_input_result, spinner_result, git_counts_result) = tokio::join!(
+    keyword_change_worker,
+    key_input_worker,
+    spinner_worker,
+    git_counts_worker
+  );
+  keyword_change_result
+    .and(key_input_result)
+    .and(spinner_result)
+    .and(git_counts_result)
     .map_err(|_| MainModeError::WorkerJoinError)?;
   result
 }
 
+fn status_text(context: &RenderContext) -> String {
+  let progress = &context.scan_progress;
+  let mut messages: Vec<String> = vec![];
+  if context.show_hidden {
+    messages.push("◌ Showing hidden".to_string());
+  }
+  if context.show_archived {
+    messages.push("◌ Showing archived".to_string());
+  }
+  if progress.is_scanning() {
+    let spinner = SPINNER_FRAMES[context.spinner_frame % SPINNER_FRAMES.len()];
+    // Naming the roots still going makes a single slow mount easy to spot
+    let pending = progress
+      .pending
+      .iter()
+      .map(|path| path.to_string_lossy())
+      .collect::<Vec<_>>();
+    messages.push(format!(
+      "{} Scanning {}/{} roots: {}",
+      spinner,
+      progress.finished(),
+      progress.total,
+      pending.join(", ")
+    ));
+  }
+  for (paths, subject, problem) in problem_paths(&progress.status) {
+    match paths.len() {
+      0 => {}
+      1 => messages.push(format!(
+        "⚠ {} {}: {}",
+        subject,
+        problem,
+        paths[0].to_string_lossy()
+      )),
+      n => messages.push(format!("⚠ {} {}s {}", n, subject, problem)),
+    }
+  }
+  messages.join("  ")
+}
+
 async fn render(mut context: RwLockWriteGuard<'_, RenderContext>) -> Result<(), MainModeError> {
   let mut stderr = stderr();
   let (width, height) = terminal::size().map_err(|_| MainModeError::TerminalSizeUnavailable)?;
@@ -234,6 +395,28 @@ async fn render(mut context: RwLockWriteGuard<'_, RenderContext>) -> Result<(),
   )
   .map_err(|_| MainModeError::StdoutWriteError)?;
 
+  let status_text = status_text(&context);
+  if !status_text.is_empty() {
+    safe_move_to(&mut stderr, 1, 4, width, height)?;
+    let available_width = (width - EXIT_MESSAGE_LEN.max(CLEAR_MESSAGE_LEN) - 3).max(0) as usize;
+    queue!(
+      stderr,
+      style::SetForegroundColor(if context.scan_progress.has_problems() {
+        style::Color::Yellow
+      } else {
+        style::Color::DarkGrey
+      }),
+      style::Print(
+        status_text
+          .chars()
+          .take(available_width)
+          .collect::<String>()
+      ),
+      style::ResetColor,
+    )
+    .map_err(|_| MainModeError::StdoutWriteError)?;
+  }
+
   safe_move_to(&mut stderr, 0, 1, width, height)?;
   let horizontal_line = safe_repeat(HORIZONTAL_LINE, width as isize - 2)?;
   queue!(
@@ -287,42 +470,121 @@ async fn render(mut context: RwLockWriteGuard<'_, RenderContext>) -> Result<(),
 
   rendering_repos.iter().enumerate().for_each(|(i, repo)| {
     safe_move_to(&mut stderr, 0, 5 + i as i16, width, height).unwrap();
-    let (before, bold, after) = split_by_matched(&repo.repo.name, &repo.matched_string);
-    if repo_selected_index == i {
+    let search_text = repo.search_text();
+    let suffix_start = repo.repo.display_name().len();
+    let indent = format!(" {}", "  ".repeat(repo.repo.depth()));
+    let selected = repo_selected_index == i;
+    // Archived entries are dimmed so they don't compete with active ones
+    let name_color = if repo.repo.archived && !selected {
+      style::Color::DarkGrey
+    } else {
+      style::Color::White
+    };
+    if selected {
       queue!(
         stderr,
-        style::Print(" "),
+        style::Print(&indent),
         style::SetBackgroundColor(BACKGROUND_PINK_COLOR),
         style::SetForegroundColor(style::Color::White),
         style::Print(" » "),
-        style::Print(before),
-        style::SetAttribute(style::Attribute::Bold),
-        style::Print(bold),
-        style::SetAttribute(style::Attribute::Reset),
-        style::SetBackgroundColor(BACKGROUND_PINK_COLOR),
-        style::SetForegroundColor(style::Color::White),
-        style::Print(after),
-        style::Print("  "),
-        style::ResetColor,
       )
       .unwrap();
     } else {
       queue!(
         stderr,
-        style::Print(" "),
+        style::Print(&indent),
         style::SetForegroundColor(LIGHTER_PINK_COLOR),
         style::Print(" • "),
         style::ResetColor,
-        style::SetForegroundColor(style::Color::White),
-        style::Print(before),
-        style::SetAttribute(style::Attribute::Bold),
-        style::Print(bold),
-        style::SetAttribute(style::Attribute::Reset),
-        style::Print(after),
-        style::ResetColor,
+        style::SetForegroundColor(name_color),
+      )
+      .unwrap();
+    }
+    let mut offset = 0;
+    for (segment, is_bold) in split_by_matched(&search_text, &repo.matched_string) {
+      let (name, suffix) = split_at_suffix(segment, offset, suffix_start);
+      offset += segment.len();
+      if is_bold {
+        queue!(stderr, style::SetAttribute(style::Attribute::Bold)).unwrap();
+      }
+      queue!(stderr, style::Print(name)).unwrap();
+      if !suffix.is_empty() {
+        // The suffix only tells same-named entries apart, keep it in the background
+        let suffix_color = if selected {
+          LIGHTER_PINK_COLOR
+        } else {
+          style::Color::DarkGrey
+        };
+        queue!(
+          stderr,
+          style::SetForegroundColor(suffix_color),
+          style::Print(suffix),
+        )
+        .unwrap();
+      }
+      queue!(stderr, style::SetAttribute(style::Attribute::Reset)).unwrap();
+      if selected {
+        queue!(stderr, style::SetBackgroundColor(BACKGROUND_PINK_COLOR)).unwrap();
+      }
+      queue!(stderr, style::SetForegroundColor(name_color)).unwrap();
+    }
+    let label_color = if selected {
+      LIGHTER_PINK_COLOR
+    } else {
+      style::Color::DarkGrey
+    };
+    if let Some(location) = repo.repo.origin_location() {
+      queue!(
+        stderr,
+        style::SetForegroundColor(label_color),
+        style::Print(format!("  {}", location)),
       )
       .unwrap();
     }
+    // Entries from other sources than the roots, e.g. an editor's recent projects
+    if repo.repo.source != DIRECTORY_SOURCE {
+      queue!(
+        stderr,
+        style::SetForegroundColor(label_color),
+        style::Print(format!("  via {}", repo.repo.source)),
+      )
+      .unwrap();
+    }
+    if !repo.repo.project_types.is_empty() {
+      let badges = repo
+        .repo
+        .project_types
+        .iter()
+        .map(|project_type| format!("[{}]", project_type.badge()))
+        .collect::<String>();
+      queue!(
+        stderr,
+        style::SetForegroundColor(label_color),
+        style::Print(format!("  {}", badges)),
+      )
+      .unwrap();
+    }
+    let git_label = repo.repo.git.as_ref().and_then(GitInfo::label);
+    let counts_label = context
+      .git_counts
+      .get(&repo.repo.path)
+      .and_then(GitCounts::label);
+    let git_label = match (git_label, counts_label) {
+      (Some(git_label), Some(counts_label)) => Some(format!("{} {}", git_label, counts_label)),
+      (git_label, counts_label) => git_label.or(counts_label),
+    };
+    if let Some(label) = git_label {
+      queue!(
+        stderr,
+        style::SetForegroundColor(label_color),
+        style::Print(format!("  {}", label)),
+      )
+      .unwrap();
+    }
+    if selected {
+      queue!(stderr, style::Print("  ")).unwrap();
+    }
+    queue!(stderr, style::ResetColor).unwrap();
   });
 
   context.repo_selected_index = repo_selected_index;
@@ -1,84 +1,87 @@
 use crate::filter::MatchedString;
 
-pub fn split_by_matched<'a>(s: &'a str, meta: &MatchedString) -> (&'a str, &'a str, &'a str) {
-  (
-    &s[..meta.matched_start],
-    &s[meta.matched_start..meta.matched_start + meta.matched_length],
-    &s[meta.matched_start + meta.matched_length..],
-  )
+// Runs of matched and unmatched characters, in order and covering the whole text
+pub fn split_by_matched<'a>(s: &'a str, meta: &MatchedString) -> Vec<(&'a str, bool)> {
+  let mut segments: Vec<(&str, bool)> = vec![];
+  let mut start = 0;
+  let mut run_matched = false;
+  for (offset, _) in s.char_indices() {
+    let matched = meta.matched_positions.binary_search(&offset).is_ok();
+    if offset > start && matched != run_matched {
+      segments.push((&s[start..offset], run_matched));
+      start = offset;
+    }
+    run_matched = matched;
+  }
+  if start < s.len() {
+    segments.push((&s[start..], run_matched));
+  }
+  segments
+}
+
+// Split a segment starting at `offset` of the whole text into the parts before and
+// after `suffix_start`
+pub fn split_at_suffix(segment: &str, offset: usize, suffix_start: usize) -> (&str, &str) {
+  segment.split_at(suffix_start.saturating_sub(offset).min(segment.len()))
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  fn matched(positions: &[usize]) -> MatchedString {
+    MatchedString {
+      matched_start: 0,
+      matched_length: 0,
+      matched_positions: positions.to_vec(),
+      distance: 0,
+    }
+  }
+
   #[test]
   fn test_split_by_matched() {
     assert_eq!(
-      split_by_matched(
-        "hello",
-        &MatchedString {
-          matched_start: 0,
-          matched_length: 1,
-          distance: 0,
-        }
-      ),
-      ("", "h", "ello")
+      split_by_matched("hello", &matched(&[0])),
+      vec![("h", true), ("ello", false)]
     );
     assert_eq!(
-      split_by_matched(
-        "hello",
-        &MatchedString {
-          matched_start: 1,
-          matched_length: 1,
-          distance: 0,
-        }
-      ),
-      ("h", "e", "llo")
+      split_by_matched("hello", &matched(&[1, 2])),
+      vec![("h", false), ("el", true), ("lo", false)]
     );
     assert_eq!(
-      split_by_matched(
-        "hello",
-        &MatchedString {
-          matched_start: 1,
-          matched_length: 2,
-          distance: 0,
-        }
-      ),
-      ("h", "el", "lo")
+      split_by_matched("hello", &matched(&[0, 1, 2, 3, 4])),
+      vec![("hello", true)]
     );
     assert_eq!(
-      split_by_matched(
-        "hello",
-        &MatchedString {
-          matched_start: 1,
-          matched_length: 3,
-          distance: 0,
-        }
-      ),
-      ("h", "ell", "o")
+      split_by_matched("hello", &matched(&[])),
+      vec![("hello", false)]
     );
+    assert_eq!(split_by_matched("", &matched(&[])), vec![]);
+  }
+
+  #[test]
+  fn split_by_scattered_matches() {
+    // `ac` on `abc` leaves the `b` out
     assert_eq!(
-      split_by_matched(
-        "hello",
-        &MatchedString {
-          matched_start: 1,
-          matched_length: 4,
-          distance: 0,
-        }
-      ),
-      ("h", "ello", "")
+      split_by_matched("abc", &matched(&[0, 2])),
+      vec![("a", true), ("b", false), ("c", true)]
     );
     assert_eq!(
-      split_by_matched(
-        "hello",
-        &MatchedString {
-          matched_start: 0,
-          matched_length: 5,
-          distance: 0,
-        }
-      ),
-      ("", "hello", "")
+      split_by_matched("a › b", &matched(&[0, 2, 6])),
+      vec![
+        ("a", true),
+        (" ", false),
+        ("›", true),
+        (" ", false),
+        ("b", true)
+      ]
     );
   }
+
+  #[test]
+  fn test_split_at_suffix() {
+    assert_eq!(split_at_suffix("api wo", 0, 3), ("api", " wo"));
+    assert_eq!(split_at_suffix("rk", 6, 3), ("", "rk"));
+    assert_eq!(split_at_suffix("ap", 0, 3), ("ap", ""));
+  }
 }
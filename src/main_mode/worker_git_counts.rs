@@ -0,0 +1,66 @@
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+use crate::{git_info::GitCounts, main_mode::ContextChange};
+
+use super::MainModeWorker;
+
+// How many repositories are walked before the list is rendered again
+const BATCH_SIZE: usize = 8;
+// How often to look for repositories that showed up in the list since
+const IDLE_INTERVAL: Duration = Duration::from_millis(200);
+
+// Repositories currently in the list are handled first, top to bottom,
+// so what the user is looking at fills in before everything else.
+pub(super) async fn git_counts(
+  MainModeWorker {
+    context,
+    contextchange_tx,
+  }: MainModeWorker,
+) {
+  let mut attempted: HashSet<PathBuf> = HashSet::new();
+  loop {
+    let paths = {
+      let context = context.read().await;
+      context
+        .repos
+        .iter()
+        .map(|found| &found.repo.path)
+        .filter(|path| !attempted.contains(*path))
+        .take(BATCH_SIZE)
+        .cloned()
+        .collect::<Vec<PathBuf>>()
+    };
+    if paths.is_empty() {
+      tokio::select! {
+        _ = contextchange_tx.closed() => break,
+        _ = tokio::time::sleep(IDLE_INTERVAL) => continue,
+      }
+    }
+    attempted.extend(paths.iter().cloned());
+
+    let reading = tokio::task::spawn_blocking(move || {
+      paths
+        .into_iter()
+        .filter_map(|path| GitCounts::read(&path).map(|counts| (path, counts)))
+        .collect::<Vec<(PathBuf, GitCounts)>>()
+    });
+    let counts = tokio::select! {
+      _ = contextchange_tx.closed() => break,
+      counts = reading => counts.unwrap_or_default(),
+    };
+    if counts.is_empty() {
+      continue;
+    }
+    {
+      let mut context = context.write().await;
+      context.git_counts.extend(counts);
+    }
+    if contextchange_tx
+      .send(ContextChange::RenderContextChanged)
+      .await
+      .is_err()
+    {
+      break;
+    }
+  }
+}
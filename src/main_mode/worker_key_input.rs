@@ -25,6 +25,29 @@ pub(super) async fn key_input(
                 contextchange_tx.send(ContextChange::Finished(Ok(None))).await.unwrap();
                 break;
               }
+              event::KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+              } => {
+                {
+                  let mut context = context.write().await;
+                  context.show_hidden = !context.show_hidden;
+                }
+                // Searching again with the same keyword picks up the new setting
+                contextchange_tx.send(ContextChange::KeywordChanged).await.unwrap();
+              }
+              event::KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+              } => {
+                {
+                  let mut context = context.write().await;
+                  context.show_archived = !context.show_archived;
+                }
+                contextchange_tx.send(ContextChange::KeywordChanged).await.unwrap();
+              }
               event::KeyEvent {
                 code: KeyCode::Esc, ..
               } => {
@@ -67,7 +90,7 @@ pub(super) async fn key_input(
               } => {
                 let context = context.read().await;
                 if let Some(repo) = context.repos.get(context.repo_selected_index) {
-                  let repo = Some(repo.repo.clone());
+                  let repo = Some(Box::new(repo.repo.clone()));
                   drop(context);
                   contextchange_tx.send(ContextChange::Finished(Ok(repo))).await.unwrap();
                   break;
@@ -1,20 +1,37 @@
-use tokio::sync::mpsc::Receiver;
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+use tokio::sync::{
+  mpsc::{self, Receiver},
+  RwLock,
+};
 
 use crate::{
-  finder::ReposFinder,
-  main_mode::{ContextChange, EscapeBehavior},
+  finder::{ReposFinder, ReposUpdate},
+  git_info::{spawn_reader, RepoDetails},
+  main_mode::{ContextChange, EscapeBehavior, RenderContext},
 };
 
 use super::MainModeWorker;
 
 pub(super) async fn keyword_change(
-  finder: ReposFinder,
+  mut finder: ReposFinder,
   mut keywordchange_rx: Receiver<String>,
+  mut reposupdate_rx: Receiver<ReposUpdate>,
   MainModeWorker {
     context,
     contextchange_tx,
   }: MainModeWorker,
 ) {
+  let (details_tx, mut details_rx) = mpsc::channel::<Vec<RepoDetails>>(20);
+  let mut details_requested: HashSet<PathBuf> = HashSet::new();
+  request_details(&finder, &mut details_requested, &details_tx);
+  report_duplicates(&finder, &context).await;
+  // Even before anything is typed, the list shows the most recently active repositories
+  update_repos(&finder, "", &context).await;
+  contextchange_tx
+    .send(ContextChange::RenderContextChanged)
+    .await
+    .unwrap();
   loop {
     tokio::select! {
       _ = contextchange_tx.closed() => {
@@ -28,19 +45,71 @@ pub(super) async fn keyword_change(
           } else {
             EscapeBehavior::Clear
           };
+          finder.set_show_hidden(context.show_hidden);
+          finder.set_show_archived(context.show_archived);
         }
-        contextchange_tx.send(ContextChange::RenderContextChanged).await.unwrap();
-        let repos = if keyword.is_empty() {
-          vec![]
-        } else {
-          finder.search_by(&keyword)
-        };
-        {
+        if contextchange_tx.send(ContextChange::RenderContextChanged).await.is_err() {
+          break;
+        }
+        update_repos(&finder, &keyword, &context).await;
+        if contextchange_tx.send(ContextChange::RenderContextChanged).await.is_err() {
+          break;
+        }
+      }
+      Some(update) = reposupdate_rx.recv() => {
+        // Re-run the current search against the new set of repositories
+        let keyword = {
           let mut context = context.write().await;
-          context.repos = repos;
+          if let ReposUpdate::RootScanned { root_path, status } = &update {
+            context.scan_progress.finish(root_path, status);
+          }
+          context.keyword.clone()
+        };
+        finder.apply(update).await;
+        report_duplicates(&finder, &context).await;
+        request_details(&finder, &mut details_requested, &details_tx);
+        update_repos(&finder, &keyword, &context).await;
+        if contextchange_tx.send(ContextChange::RenderContextChanged).await.is_err() {
+          break;
+        }
+      }
+      Some(details) = details_rx.recv() => {
+        finder.set_details(details);
+        let keyword = context.read().await.keyword.clone();
+        update_repos(&finder, &keyword, &context).await;
+        if contextchange_tx.send(ContextChange::RenderContextChanged).await.is_err() {
+          break;
         }
-        contextchange_tx.send(ContextChange::RenderContextChanged).await.unwrap();
       }
     }
   }
 }
+
+// Read details of newly discovered repositories in the background, each one only once
+fn request_details(
+  finder: &ReposFinder,
+  requested: &mut HashSet<PathBuf>,
+  details_tx: &mpsc::Sender<Vec<RepoDetails>>,
+) {
+  let paths = finder
+    .listup_repos()
+    .into_iter()
+    .filter(|repo| requested.insert(repo.path.clone()))
+    .map(|repo| repo.path)
+    .collect::<Vec<PathBuf>>();
+  if !paths.is_empty() {
+    spawn_reader(paths, details_tx.clone());
+  }
+}
+
+async fn report_duplicates(finder: &ReposFinder, context: &Arc<RwLock<RenderContext>>) {
+  context.write().await.scan_progress.status.duplicates = finder.duplicates().to_vec();
+}
+
+async fn update_repos(finder: &ReposFinder, keyword: &str, context: &Arc<RwLock<RenderContext>>) {
+  let repos = finder.search_by(keyword);
+  {
+    let mut context = context.write().await;
+    context.repos = repos;
+  }
+}
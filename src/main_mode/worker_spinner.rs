@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use crate::main_mode::ContextChange;
+
+use super::MainModeWorker;
+
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(super) async fn spinner(
+  MainModeWorker {
+    context,
+    contextchange_tx,
+  }: MainModeWorker,
+) {
+  loop {
+    tokio::select! {
+      _ = contextchange_tx.closed() => {
+        break;
+      }
+      _ = tokio::time::sleep(SPINNER_INTERVAL) => {
+        {
+          let mut context = context.write().await;
+          if !context.scan_progress.is_scanning() {
+            continue;
+          }
+          context.spinner_frame = context.spinner_frame.wrapping_add(1);
+        }
+        // The render loop is gone once an entry is picked, even while roots are still scanning
+        if contextchange_tx.send(ContextChange::RenderContextChanged).await.is_err() {
+          break;
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use crate::finder::read_entries;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProjectType {
+  Rust,
+  Node,
+  Go,
+  Python,
+  Ruby,
+  Java,
+  Elixir,
+  Haskell,
+  C,
+  Swift,
+  Dart,
+  Php,
+  Dotnet,
+  Zig,
+  Nix,
+  Docker,
+}
+
+// Files at the top of a repository telling what kind of project it is.
+// A leading `*` matches any file name with that suffix.
+const MARKERS: &[(&str, ProjectType)] = &[
+  ("Cargo.toml", ProjectType::Rust),
+  ("package.json", ProjectType::Node),
+  ("go.mod", ProjectType::Go),
+  ("pyproject.toml", ProjectType::Python),
+  ("setup.py", ProjectType::Python),
+  ("requirements.txt", ProjectType::Python),
+  ("Pipfile", ProjectType::Python),
+  ("Gemfile", ProjectType::Ruby),
+  ("pom.xml", ProjectType::Java),
+  ("build.gradle", ProjectType::Java),
+  ("build.gradle.kts", ProjectType::Java),
+  ("mix.exs", ProjectType::Elixir),
+  ("stack.yaml", ProjectType::Haskell),
+  ("cabal.project", ProjectType::Haskell),
+  ("*.cabal", ProjectType::Haskell),
+  ("CMakeLists.txt", ProjectType::C),
+  ("meson.build", ProjectType::C),
+  ("Package.swift", ProjectType::Swift),
+  ("pubspec.yaml", ProjectType::Dart),
+  ("composer.json", ProjectType::Php),
+  ("*.csproj", ProjectType::Dotnet),
+  ("*.fsproj", ProjectType::Dotnet),
+  ("*.sln", ProjectType::Dotnet),
+  ("build.zig", ProjectType::Zig),
+  ("flake.nix", ProjectType::Nix),
+  ("default.nix", ProjectType::Nix),
+  ("shell.nix", ProjectType::Nix),
+  ("Dockerfile", ProjectType::Docker),
+  ("compose.yml", ProjectType::Docker),
+  ("compose.yaml", ProjectType::Docker),
+  ("docker-compose.yml", ProjectType::Docker),
+  ("docker-compose.yaml", ProjectType::Docker),
+];
+
+const ALL: &[ProjectType] = &[
+  ProjectType::Rust,
+  ProjectType::Node,
+  ProjectType::Go,
+  ProjectType::Python,
+  ProjectType::Ruby,
+  ProjectType::Java,
+  ProjectType::Elixir,
+  ProjectType::Haskell,
+  ProjectType::C,
+  ProjectType::Swift,
+  ProjectType::Dart,
+  ProjectType::Php,
+  ProjectType::Dotnet,
+  ProjectType::Zig,
+  ProjectType::Nix,
+  ProjectType::Docker,
+];
+
+impl ProjectType {
+  // Used in the query (`type:rust`) and in the index
+  pub fn name(&self) -> &'static str {
+    match self {
+      ProjectType::Rust => "rust",
+      ProjectType::Node => "node",
+      ProjectType::Go => "go",
+      ProjectType::Python => "python",
+      ProjectType::Ruby => "ruby",
+      ProjectType::Java => "java",
+      ProjectType::Elixir => "elixir",
+      ProjectType::Haskell => "haskell",
+      ProjectType::C => "c",
+      ProjectType::Swift => "swift",
+      ProjectType::Dart => "dart",
+      ProjectType::Php => "php",
+      ProjectType::Dotnet => "dotnet",
+      ProjectType::Zig => "zig",
+      ProjectType::Nix => "nix",
+      ProjectType::Docker => "docker",
+    }
+  }
+
+  // Short label shown next to the name in the list
+  pub fn badge(&self) -> &'static str {
+    match self {
+      ProjectType::Rust => "rs",
+      ProjectType::Node => "js",
+      ProjectType::Python => "py",
+      ProjectType::Ruby => "rb",
+      ProjectType::Elixir => "ex",
+      ProjectType::Haskell => "hs",
+      ProjectType::Dotnet => "net",
+      ProjectType::Docker => "dkr",
+      other => other.name(),
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    ALL
+      .iter()
+      .find(|project_type| project_type.name() == name)
+      .copied()
+  }
+
+  // Either the name or the badge, so `type:ru` and `type:rs` both narrow to Rust
+  pub fn matches(&self, filter: &str) -> bool {
+    self.name().starts_with(filter) || self.badge().starts_with(filter)
+  }
+}
+
+pub fn project_types_of(file_names: impl Iterator<Item = String>) -> Vec<ProjectType> {
+  let mut types: Vec<ProjectType> = vec![];
+  for file_name in file_names {
+    for (marker, project_type) in MARKERS {
+      let matched = match marker.strip_prefix('*') {
+        Some(suffix) => file_name.ends_with(suffix),
+        None => file_name == *marker,
+      };
+      if matched && !types.contains(project_type) {
+        types.push(*project_type);
+      }
+    }
+  }
+  types.sort();
+  types
+}
+
+pub async fn detect_project_types(path: &Path) -> Vec<ProjectType> {
+  // Not being able to look inside only means no badges
+  let entries = read_entries(path, &mut vec![]).await.unwrap_or_default();
+  project_types_of(
+    entries
+      .into_iter()
+      .map(|(_, name)| name.to_string_lossy().into_owned()),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn types(file_names: &[&str]) -> Vec<ProjectType> {
+    project_types_of(file_names.iter().map(|name| name.to_string()))
+  }
+
+  #[test]
+  fn test_project_types_of() {
+    assert_eq!(
+      types(&["README.md", "package.json", "Cargo.toml", "Cargo.lock"]),
+      vec![ProjectType::Rust, ProjectType::Node]
+    );
+    assert_eq!(
+      types(&["App.csproj", "App.sln", "Dockerfile"]),
+      vec![ProjectType::Dotnet, ProjectType::Docker]
+    );
+    assert_eq!(types(&["src", "LICENSE"]), vec![]);
+  }
+
+  #[test]
+  fn test_matches() {
+    assert!(ProjectType::Rust.matches("ru"));
+    assert!(ProjectType::Rust.matches("rs"));
+    assert!(!ProjectType::Rust.matches("py"));
+    assert_eq!(ProjectType::from_name("dotnet"), Some(ProjectType::Dotnet));
+    assert_eq!(ProjectType::from_name("cobol"), None);
+  }
+}
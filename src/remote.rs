@@ -0,0 +1,107 @@
+use crate::git_config::GitConfig;
+
+// A remote URL split into the parts popi shows and filters by
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Remote {
+  pub url: String,
+  pub host: String,
+  // Everything between host and name, e.g. `group/subgroup` on GitLab
+  pub owner: String,
+  pub name: String,
+}
+
+impl Remote {
+  pub fn origin(config: &GitConfig) -> Option<Self> {
+    Self::parse(config.get("remote", Some("origin"), "url")?)
+  }
+
+  // Understands `https://host/owner/name.git`, `ssh://user@host:port/owner/name`
+  // and the scp-like `user@host:owner/name.git`. Local paths give None.
+  pub fn parse(url: &str) -> Option<Self> {
+    let (authority, path) = match url.split_once("://") {
+      Some((scheme, rest)) => {
+        if scheme == "file" {
+          return None;
+        }
+        rest.split_once('/')?
+      }
+      None => {
+        let (authority, path) = url.split_once(':')?;
+        // `C:/path` on Windows and `./dir:name` are paths, not hosts
+        if authority.len() < 2 || authority.contains('/') {
+          return None;
+        }
+        (authority, path)
+      }
+    };
+    let host = authority.rsplit('@').next()?;
+    // A port only follows the host in URL form, scp-like paths can not carry one
+    let host = host.split(':').next()?.to_lowercase();
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, name) = path.rsplit_once('/')?;
+    if host.is_empty() || owner.is_empty() || name.is_empty() {
+      return None;
+    }
+    Some(Remote {
+      url: url.to_string(),
+      host,
+      owner: owner.to_string(),
+      name: name.to_string(),
+    })
+  }
+
+  // `github.com/owner`, shown next to the repository name
+  pub fn location(&self) -> String {
+    format!("{}/{}", self.host, self.owner)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parsed(url: &str) -> Option<(String, String, String)> {
+    Remote::parse(url).map(|remote| (remote.host, remote.owner, remote.name))
+  }
+
+  fn parts(host: &str, owner: &str, name: &str) -> Option<(String, String, String)> {
+    Some((host.to_string(), owner.to_string(), name.to_string()))
+  }
+
+  #[test]
+  fn parse_urls() {
+    assert_eq!(
+      parsed("https://github.com/aspulse/popi.git"),
+      parts("github.com", "aspulse", "popi")
+    );
+    assert_eq!(
+      parsed("https://user@GitHub.com/aspulse/popi/"),
+      parts("github.com", "aspulse", "popi")
+    );
+    assert_eq!(
+      parsed("git@github.com:aspulse/popi.git"),
+      parts("github.com", "aspulse", "popi")
+    );
+    assert_eq!(
+      parsed("ssh://git@gitlab.example.com:2222/group/sub/tool.git"),
+      parts("gitlab.example.com", "group/sub", "tool")
+    );
+    assert_eq!(parsed("/srv/git/popi.git"), None);
+    assert_eq!(parsed("file:///srv/git/popi.git"), None);
+    assert_eq!(parsed("../popi"), None);
+    assert_eq!(parsed("C:/repos/popi"), None);
+  }
+
+  #[test]
+  fn read_origin() {
+    let config = GitConfig::parse(
+      "[remote \"upstream\"]\n\turl = https://github.com/upstream/popi\n\
+       [remote \"origin\"]\n\turl = git@github.com:fork/popi.git\n",
+    );
+    assert_eq!(
+      Remote::origin(&config).map(|remote| remote.location()),
+      Some("github.com/fork".to_string())
+    );
+  }
+}
@@ -0,0 +1,40 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::config::{FinderOptions, RepoRoot};
+use crate::finder::{scan_root, RepoStatus};
+
+// Tag of entries found by scanning the configured roots
+pub const DIRECTORY_SOURCE: &str = "directory";
+
+pub type SourceFuture<'a> = Pin<Box<dyn Future<Output = RepoStatus> + Send + 'a>>;
+
+// Somewhere repositories can be listed from, e.g. a list file or another tool's database.
+// Extra sources are merged into the results with `ReposFinder::add_source`.
+pub trait RepoSource: Send + Sync {
+  // Put on every entry the source lists, see `Repo::source`
+  fn name(&self) -> &str;
+  fn list(&self) -> SourceFuture<'_>;
+}
+
+// The scan of a single configured root
+pub struct DirectorySource {
+  pub root: RepoRoot,
+  pub options: FinderOptions,
+}
+
+impl DirectorySource {
+  pub fn new(root: RepoRoot, options: FinderOptions) -> Self {
+    DirectorySource { root, options }
+  }
+}
+
+impl RepoSource for DirectorySource {
+  fn name(&self) -> &str {
+    DIRECTORY_SOURCE
+  }
+
+  fn list(&self) -> SourceFuture<'_> {
+    Box::pin(scan_root(self.root.clone(), self.options.clone()))
+  }
+}
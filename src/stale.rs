@@ -0,0 +1,411 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future::join_all;
+
+use crate::cli::StaleArgs;
+use crate::config::LocalStorage;
+use crate::finder::{read_permit, Repo, RepoKind, ReposFinder, ReposStatus};
+use crate::git_info::{last_active, GitCounts, GitInfo, GitRepo};
+use crate::main_mode::problem_paths;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// A repository nobody has worked on for a while, with what deleting it would lose
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleRepo {
+  pub path: PathBuf,
+  pub name: String,
+  // Unix time in seconds, see `git_info::last_active`
+  pub last_active: i64,
+  // Bytes allocated for everything below path, symlinks are not followed
+  pub size: u64,
+  // Read from `.git`, None for plain directories
+  pub git: Option<GitState>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitState {
+  // The short hash while HEAD is detached
+  pub branch: Option<String>,
+  pub dirty: bool,
+  // Commits not on the upstream yet, None when the branch has no upstream
+  pub unpushed: Option<usize>,
+  pub stashes: usize,
+}
+
+impl GitState {
+  // Anything that only exists in this checkout
+  pub fn has_unsaved_work(&self) -> bool {
+    self.dirty || self.stashes > 0 || self.unpushed != Some(0)
+  }
+
+  // `dirty, ↑2, ≡1`, or `clean` when everything is pushed
+  fn summary(&self) -> String {
+    let mut parts: Vec<String> = vec![];
+    if self.dirty {
+      parts.push("dirty".to_string());
+    }
+    match self.unpushed {
+      Some(0) => {}
+      Some(unpushed) => parts.push(format!("↑{}", unpushed)),
+      None => parts.push("no upstream".to_string()),
+    }
+    if self.stashes > 0 {
+      parts.push(format!("≡{}", self.stashes));
+    }
+    match parts.is_empty() {
+      true => "clean".to_string(),
+      false => parts.join(", "),
+    }
+  }
+}
+
+pub async fn run(storage: &LocalStorage, args: &StaleArgs) {
+  let mut finder =
+    ReposFinder::new_with_options(storage.repo_paths.to_vec(), storage.finder_options.clone());
+  let status = finder.init().await;
+  // The list leaves out whatever these roots hold, stdout stays as it is for `--json`
+  for line in problem_lines(&status) {
+    eprintln!("{}", line);
+  }
+  let now = unix_now();
+  let repos = stale_repos(&finder, args.days, now).await;
+  if args.json {
+    println!("{}", to_json(&repos, now));
+  } else if repos.is_empty() {
+    println!(" No repository untouched for {} days.", args.days);
+  } else {
+    print!("{}", to_table(&repos, now));
+  }
+}
+
+// Roots that could not be scanned fully, worded like the picker's status line
+fn problem_lines(status: &ReposStatus) -> Vec<String> {
+  problem_paths(status)
+    .into_iter()
+    .flat_map(|(paths, subject, problem)| {
+      paths
+        .into_iter()
+        .map(move |path| format!(" ⚠ {} {}: {}", subject, problem, path.to_string_lossy()))
+    })
+    .collect()
+}
+
+// Repositories and worktrees last active more than `days` ago, largest first. Submodules and
+// workspace members are part of their parent's directory and left out.
+pub async fn stale_repos(finder: &ReposFinder, days: u64, now: i64) -> Vec<StaleRepo> {
+  let max_age = i64::try_from(days)
+    .unwrap_or(i64::MAX)
+    .saturating_mul(SECONDS_PER_DAY);
+  let cutoff = now.saturating_sub(max_age);
+  let reading = finder
+    .listup_repos()
+    .into_iter()
+    .filter(|repo| matches!(repo.kind, RepoKind::Repository | RepoKind::Worktree { .. }))
+    .map(|repo| async move {
+      let _permit = read_permit().await;
+      tokio::task::spawn_blocking(move || read_stale(&repo, cutoff))
+        .await
+        .ok()
+        .flatten()
+    });
+  let mut repos = join_all(reading)
+    .await
+    .into_iter()
+    .flatten()
+    .collect::<Vec<StaleRepo>>();
+  repos.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+  repos
+}
+
+// Only walks the directory once the repository turns out to be stale, sizes take the longest
+fn read_stale(repo: &Repo, cutoff: i64) -> Option<StaleRepo> {
+  let git_repo = GitRepo::open(&repo.path);
+  let info = git_repo.as_ref().and_then(GitInfo::read_from);
+  let last_active = last_active(&repo.path, git_repo.as_ref(), info.as_ref())?;
+  if last_active >= cutoff {
+    return None;
+  }
+  let git = git_repo.zip(info).map(|(git_repo, info)| {
+    let counts = GitCounts::read_from(&git_repo);
+    GitState {
+      branch: info
+        .branch
+        .or_else(|| info.commit.map(|commit| commit.chars().take(7).collect())),
+      dirty: info.dirty,
+      unpushed: counts.ahead_behind.map(|(ahead, _)| ahead),
+      stashes: counts.stashes,
+    }
+  });
+  Some(StaleRepo {
+    path: repo.path.clone(),
+    name: repo.display_name(),
+    last_active,
+    size: disk_usage(&repo.path),
+    git,
+  })
+}
+
+fn disk_usage(path: &Path) -> u64 {
+  let mut size = 0;
+  let mut pending = vec![path.to_path_buf()];
+  while let Some(path) = pending.pop() {
+    let Ok(metadata) = fs::symlink_metadata(&path) else {
+      continue;
+    };
+    size += allocated_size(&metadata);
+    if metadata.is_dir() {
+      if let Ok(entries) = fs::read_dir(&path) {
+        pending.extend(
+          entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path()),
+        );
+      }
+    }
+  }
+  size
+}
+
+// Sparse and compressed files take less than their length
+#[cfg(unix)]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+  use std::os::unix::fs::MetadataExt;
+  metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+  metadata.len()
+}
+
+fn unix_now() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+fn idle_days(repo: &StaleRepo, now: i64) -> i64 {
+  (now - repo.last_active) / SECONDS_PER_DAY
+}
+
+// `1.5 GiB`
+pub fn format_size(bytes: u64) -> String {
+  const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+  if bytes < 1024 {
+    return format!("{} B", bytes);
+  }
+  let mut size = bytes as f64 / 1024.0;
+  let mut unit = 0;
+  while size >= 1024.0 && unit < UNITS.len() - 1 {
+    size /= 1024.0;
+    unit += 1;
+  }
+  format!("{:.1} {}", size, UNITS[unit])
+}
+
+pub fn to_table(repos: &[StaleRepo], now: i64) -> String {
+  let mut rows = vec![[
+    "SIZE".to_string(),
+    "IDLE".to_string(),
+    "BRANCH".to_string(),
+    "WORK".to_string(),
+    "PATH".to_string(),
+  ]];
+  for repo in repos {
+    let (branch, work) = match &repo.git {
+      Some(git) => (
+        git.branch.clone().unwrap_or_else(|| "-".to_string()),
+        git.summary(),
+      ),
+      None => ("-".to_string(), "not a git repository".to_string()),
+    };
+    rows.push([
+      format_size(repo.size),
+      format!("{}d", idle_days(repo, now)),
+      branch,
+      work,
+      repo.path.to_string_lossy().into_owned(),
+    ]);
+  }
+  let mut widths = [0; 5];
+  for row in &rows {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.chars().count());
+    }
+  }
+  let mut table = String::new();
+  for row in &rows {
+    let cells = row
+      .iter()
+      .zip(widths)
+      .enumerate()
+      .map(|(i, (cell, width))| {
+        let padding = " ".repeat(width - cell.chars().count());
+        match i {
+          // Sizes line up on the right, everything else on the left
+          0 => format!("{}{}", padding, cell),
+          4 => cell.clone(),
+          _ => format!("{}{}", cell, padding),
+        }
+      })
+      .collect::<Vec<String>>();
+    table.push_str(&format!(" {}\n", cells.join("  ")));
+  }
+  table
+}
+
+pub fn to_json(repos: &[StaleRepo], now: i64) -> String {
+  let entries = repos
+    .iter()
+    .map(|repo| {
+      let mut fields = vec![
+        ("path", json_string(&repo.path.to_string_lossy())),
+        ("name", json_string(&repo.name)),
+        ("size", repo.size.to_string()),
+        ("last_active", repo.last_active.to_string()),
+        ("idle_days", idle_days(repo, now).to_string()),
+      ];
+      match &repo.git {
+        Some(git) => fields.extend([
+          ("git", "true".to_string()),
+          (
+            "branch",
+            git
+              .branch
+              .as_deref()
+              .map_or("null".to_string(), json_string),
+          ),
+          ("dirty", git.dirty.to_string()),
+          (
+            "unpushed",
+            git
+              .unpushed
+              .map_or("null".to_string(), |unpushed| unpushed.to_string()),
+          ),
+          ("stashes", git.stashes.to_string()),
+          ("unsaved_work", git.has_unsaved_work().to_string()),
+        ]),
+        None => fields.push(("git", "false".to_string())),
+      }
+      let fields = fields
+        .into_iter()
+        .map(|(key, value)| format!("\"{}\": {}", key, value))
+        .collect::<Vec<String>>();
+      format!("  {{{}}}", fields.join(", "))
+    })
+    .collect::<Vec<String>>();
+  match entries.is_empty() {
+    true => "[]".to_string(),
+    false => format!("[\n{}\n]", entries.join(",\n")),
+  }
+}
+
+fn json_string(text: &str) -> String {
+  let mut escaped = String::from("\"");
+  for c in text.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      '\r' => escaped.push_str("\\r"),
+      '\t' => escaped.push_str("\\t"),
+      c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+  escaped.push('"');
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stale_repo(path: &str, git: Option<GitState>) -> StaleRepo {
+    StaleRepo {
+      path: PathBuf::from(path),
+      name: path.rsplit('/').next().unwrap().to_string(),
+      last_active: 0,
+      size: 1536,
+      git,
+    }
+  }
+
+  #[test]
+  fn test_format_size() {
+    assert_eq!(format_size(512), "512 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+  }
+
+  #[test]
+  fn test_to_table() {
+    let repos = vec![
+      stale_repo(
+        "/src/popi",
+        Some(GitState {
+          branch: Some("main".to_string()),
+          dirty: true,
+          unpushed: Some(2),
+          stashes: 0,
+        }),
+      ),
+      stale_repo("/src/notes", None),
+    ];
+    assert_eq!(
+      to_table(&repos, 100 * SECONDS_PER_DAY),
+      concat!(
+        "    SIZE  IDLE  BRANCH  WORK                  PATH\n",
+        " 1.5 KiB  100d  main    dirty, ↑2             /src/popi\n",
+        " 1.5 KiB  100d  -       not a git repository  /src/notes\n",
+      )
+    );
+  }
+
+  #[test]
+  fn test_problem_lines() {
+    let status = ReposStatus {
+      paths_not_found: vec![PathBuf::from("/src/gone")],
+      paths_timed_out: vec![PathBuf::from("/mnt/nfs")],
+      paths_permission_denied: vec![PathBuf::from("/src/private")],
+      ..Default::default()
+    };
+    assert_eq!(
+      problem_lines(&status),
+      vec![
+        " ⚠ root not found: /src/gone",
+        " ⚠ root timed out: /mnt/nfs",
+        " ⚠ path permission denied: /src/private",
+      ]
+    );
+    assert!(problem_lines(&ReposStatus::default()).is_empty());
+  }
+
+  #[test]
+  fn test_to_json() {
+    let repos = vec![
+      stale_repo(
+        "/src/\"quoted\"",
+        Some(GitState {
+          branch: None,
+          dirty: false,
+          unpushed: Some(0),
+          stashes: 0,
+        }),
+      ),
+      stale_repo("/src/notes", None),
+    ];
+    assert_eq!(
+      to_json(&repos, SECONDS_PER_DAY),
+      "[\n  {\"path\": \"/src/\\\"quoted\\\"\", \"name\": \"\\\"quoted\\\"\", \"size\": 1536, \
+       \"last_active\": 0, \"idle_days\": 1, \"git\": true, \"branch\": null, \"dirty\": false, \
+       \"unpushed\": 0, \"stashes\": 0, \"unsaved_work\": false},\n  \
+       {\"path\": \"/src/notes\", \"name\": \"notes\", \"size\": 1536, \"last_active\": 0, \
+       \"idle_days\": 1, \"git\": false}\n]"
+    );
+    assert_eq!(to_json(&[], 0), "[]");
+  }
+}
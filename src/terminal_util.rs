@@ -1,4 +1,5 @@
-use std::io::{stderr, stdin, Write};
+use std::io::{stderr, stdin, stdout, Write};
+use std::path::Path;
 
 pub const VERTICAL_LINE: &str = "│";
 pub const HORIZONTAL_LINE: &str = "─";
@@ -6,6 +7,7 @@ pub const TOP_LEFT_CORNER: &str = "╭";
 pub const TOP_RIGHT_CORNER: &str = "╮";
 pub const BOTTOM_LEFT_CORNER: &str = "╰";
 pub const BOTTOM_RIGHT_CORNER: &str = "╯";
+pub const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
 pub fn yes_or_no(question: String) -> bool {
   let mut answer = String::new();
@@ -22,3 +24,17 @@ pub fn yes_or_no(question: String) -> bool {
     }
   }
 }
+
+// Shell wrappers `cd` into whatever is printed, so the path goes out byte for byte
+pub fn print_path(path: &Path) -> std::io::Result<()> {
+  let mut stdout = stdout().lock();
+  #[cfg(unix)]
+  {
+    use std::os::unix::ffi::OsStrExt;
+    stdout.write_all(path.as_os_str().as_bytes())?;
+  }
+  #[cfg(not(unix))]
+  stdout.write_all(path.to_string_lossy().as_bytes())?;
+  stdout.write_all(b"\n")?;
+  stdout.flush()
+}
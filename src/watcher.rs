@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::config::{FinderOptions, RepoRoot, RootLayout};
+use crate::finder::{is_dir, only_dirs, read_entries, scan_root, RepoStatus, ReposUpdate};
+
+// Events usually come in bursts (e.g. `git clone` creating a directory and renaming it),
+// wait a little so one burst triggers only one rescan per root.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// Watch every root for repositories being added or removed while main mode is running.
+// Only the directory levels that hold repositories are watched, never the repositories
+// themselves, so the number of watches stays small.
+pub fn spawn_watcher(
+  roots: Vec<RepoRoot>,
+  options: FinderOptions,
+  reposupdate_tx: mpsc::Sender<ReposUpdate>,
+) -> notify::Result<JoinHandle<()>> {
+  let (changed_tx, mut changed_rx) = mpsc::unbounded_channel::<usize>();
+  let watched_roots = roots.clone();
+  let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+    let Ok(event) = event else {
+      return;
+    };
+    if !is_listing_change(&event.kind) {
+      return;
+    }
+    for path in &event.paths {
+      if let Some(index) = owning_root(&watched_roots, path) {
+        let _ = changed_tx.send(index);
+      }
+    }
+  })?;
+
+  let watcher = Arc::new(Mutex::new(watcher));
+  Ok(tokio::spawn(async move {
+    for root in &roots {
+      watch_root_in_background(&watcher, root, &options).await;
+    }
+    while let Some(index) = changed_rx.recv().await {
+      tokio::time::sleep(DEBOUNCE).await;
+      let mut changed: Vec<usize> = vec![index];
+      while let Ok(index) = changed_rx.try_recv() {
+        if !changed.contains(&index) {
+          changed.push(index);
+        }
+      }
+      for index in changed {
+        let root = &roots[index];
+        let repos = match scan_root(root.clone(), options.clone()).await {
+          RepoStatus::Found(repos, _) => repos,
+          RepoStatus::NotFound(_) => vec![],
+          RepoStatus::TimedOut(_) => continue,
+        };
+        // New host or owner directories of a ghq root need watches of their own
+        watch_root_in_background(&watcher, root, &options).await;
+        let update = ReposUpdate::RootReplaced {
+          root_path: root.path.clone(),
+          repos,
+        };
+        if reposupdate_tx.send(update).await.is_err() {
+          return;
+        }
+      }
+    }
+  }))
+}
+
+fn is_listing_change(kind: &EventKind) -> bool {
+  matches!(
+    kind,
+    EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+  )
+}
+
+// The most specific root containing the path, roots may be nested in each other
+fn owning_root(roots: &[RepoRoot], path: &Path) -> Option<usize> {
+  roots
+    .iter()
+    .enumerate()
+    .filter(|(_, root)| path.starts_with(&root.path))
+    .max_by_key(|(_, root)| root.path.components().count())
+    .map(|(index, _)| index)
+}
+
+// Listing the directories to watch blocks for as long as a mount hangs, so it is done before
+// taking the watcher's lock and gives up after the root's timeout. Adding the watches may
+// hang too; that happens on a blocking thread the task stops waiting for after the timeout,
+// so neither the runtime nor the rescans of other roots are held up.
+async fn watch_root_in_background(
+  watcher: &Arc<Mutex<RecommendedWatcher>>,
+  root: &RepoRoot,
+  options: &FinderOptions,
+) {
+  let timeout = root.timeout(options);
+  let Ok(paths) = tokio::time::timeout(timeout, watched_dirs(root)).await else {
+    return;
+  };
+  let watcher = watcher.clone();
+  let watching = tokio::task::spawn_blocking(move || {
+    if let Ok(mut watcher) = watcher.lock() {
+      for path in paths {
+        if watcher.watch(&path, RecursiveMode::NonRecursive).is_err() {
+          break;
+        }
+      }
+    }
+  });
+  let _ = tokio::time::timeout(timeout, watching).await;
+}
+
+async fn watched_dirs(root: &RepoRoot) -> Vec<PathBuf> {
+  if !is_dir(&root.path).await {
+    // A missing root cannot be watched, it is simply reported as not found on startup
+    return vec![];
+  }
+  let mut paths = vec![root.path.clone()];
+  if root.layout == RootLayout::Ghq {
+    for host_path in sub_dirs(&root.path).await {
+      let owner_paths = sub_dirs(&host_path).await;
+      paths.push(host_path);
+      paths.extend(owner_paths);
+    }
+  }
+  paths
+}
+
+async fn sub_dirs(path: &Path) -> Vec<PathBuf> {
+  let entries = read_entries(path, &mut vec![]).await.unwrap_or_default();
+  only_dirs(entries)
+    .await
+    .into_iter()
+    .map(|(path, _)| path)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_owning_root() {
+    let roots = vec![
+      RepoRoot::from(PathBuf::from("/home/aspulse/repos")),
+      RepoRoot::from(PathBuf::from("/home/aspulse/repos/work")),
+      RepoRoot::from(PathBuf::from("/home/aspulse/ghq")),
+    ];
+    assert_eq!(
+      owning_root(&roots, Path::new("/home/aspulse/repos/popi")),
+      Some(0)
+    );
+    assert_eq!(
+      owning_root(&roots, Path::new("/home/aspulse/repos/work/api")),
+      Some(1)
+    );
+    assert_eq!(
+      owning_root(&roots, Path::new("/home/aspulse/ghq/github.com")),
+      Some(2)
+    );
+    assert_eq!(owning_root(&roots, Path::new("/tmp/popi")), None);
+  }
+}
@@ -0,0 +1,331 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::finder::{file_id, is_dir, is_file, read_entries};
+
+// `**` in member patterns goes at most this deep, workspaces are not that nested
+const MAX_GLOB_DEPTH: usize = 4;
+
+// Member patterns of the workspaces declared in a repository, each with the manifest
+// a matching directory needs to have to count as a member
+struct MemberPatterns {
+  manifest: &'static str,
+  include: Vec<String>,
+  exclude: Vec<String>,
+}
+
+// Directories of Cargo, npm/yarn and pnpm workspace members, relative to the repository
+// and separated by `/`, e.g. `crates/foo`. Sorted, the repository itself is never a member.
+pub async fn workspace_members(repo_path: &Path) -> Vec<String> {
+  let mut declared: Vec<MemberPatterns> = vec![];
+  if let Ok(cargo_toml) = async_fs::read_to_string(repo_path.join("Cargo.toml")).await {
+    declared.extend(cargo_members(&cargo_toml));
+  }
+  if let Ok(package_json) = async_fs::read_to_string(repo_path.join("package.json")).await {
+    declared.extend(npm_members(&package_json));
+  }
+  if let Ok(pnpm_workspace) = async_fs::read_to_string(repo_path.join("pnpm-workspace.yaml")).await
+  {
+    declared.extend(pnpm_members(&pnpm_workspace));
+  }
+
+  let mut members: Vec<String> = vec![];
+  for patterns in declared {
+    for include in &patterns.include {
+      let components = pattern_components(include);
+      let ancestors = file_id(repo_path).await.into_iter().collect::<Vec<_>>();
+      for member in expand(
+        repo_path.to_path_buf(),
+        &components,
+        MAX_GLOB_DEPTH,
+        ancestors,
+      )
+      .await
+      {
+        let Ok(relative) = member.strip_prefix(repo_path) else {
+          continue;
+        };
+        let relative = relative
+          .components()
+          .map(|component| component.as_os_str().to_string_lossy())
+          .collect::<Vec<_>>()
+          .join("/");
+        let excluded = patterns
+          .exclude
+          .iter()
+          .any(|exclude| glob_matches(&pattern_components(exclude), &relative));
+        if !relative.is_empty() && !excluded && is_file(&member.join(patterns.manifest)).await {
+          members.push(relative);
+        }
+      }
+    }
+  }
+  members.sort();
+  members.dedup();
+  members
+}
+
+// `members` and `exclude` of the `[workspace]` table. Only arrays of plain strings are
+// understood, which is what Cargo accepts there anyway.
+fn cargo_members(content: &str) -> Option<MemberPatterns> {
+  let mut found = false;
+  let mut in_workspace = false;
+  // Set while inside an array spanning several lines
+  let mut open_array: Option<bool> = None;
+  let mut include: Vec<String> = vec![];
+  let mut exclude: Vec<String> = vec![];
+  for line in content.lines() {
+    let line = strip_comment(line).trim();
+    let (is_members, values) = match open_array {
+      Some(is_members) => (is_members, line),
+      None => {
+        if line.starts_with('[') {
+          in_workspace = line == "[workspace]";
+          found |= in_workspace;
+          continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_workspace) else {
+          continue;
+        };
+        match key.trim() {
+          "members" => (true, value.trim()),
+          "exclude" => (false, value.trim()),
+          _ => continue,
+        }
+      }
+    };
+    let target = if is_members {
+      &mut include
+    } else {
+      &mut exclude
+    };
+    target.extend(quoted_strings(values));
+    open_array = (!values.contains(']')).then_some(is_members);
+  }
+  found.then_some(MemberPatterns {
+    manifest: "Cargo.toml",
+    include,
+    exclude,
+  })
+}
+
+// The line up to a `#` that is not inside a string
+fn strip_comment(line: &str) -> &str {
+  let mut quote: Option<char> = None;
+  let mut escaped = false;
+  for (index, c) in line.char_indices() {
+    match quote {
+      // Only basic strings have escapes, `'literal'` ones end at the next quote
+      Some('"') if escaped => escaped = false,
+      Some('"') if c == '\\' => escaped = true,
+      Some(open) if c == open => quote = None,
+      Some(_) => {}
+      None if c == '"' || c == '\'' => quote = Some(c),
+      None if c == '#' => return &line[..index],
+      None => {}
+    }
+  }
+  line
+}
+
+fn quoted_strings(text: &str) -> Vec<String> {
+  text
+    .split(['"', '\''])
+    .skip(1)
+    .step_by(2)
+    .map(str::to_string)
+    .collect()
+}
+
+// `workspaces` of `package.json`, either a list of patterns or `{ "packages": [...] }`
+fn npm_members(content: &str) -> Option<MemberPatterns> {
+  let package = YamlLoader::load_from_str(content)
+    .ok()?
+    .into_iter()
+    .next()?;
+  let workspaces = match &package["workspaces"] {
+    Yaml::Array(patterns) => patterns,
+    workspaces => workspaces["packages"].as_vec()?,
+  };
+  Some(negatable_patterns("package.json", workspaces))
+}
+
+// `packages` of `pnpm-workspace.yaml`
+fn pnpm_members(content: &str) -> Option<MemberPatterns> {
+  let workspace = YamlLoader::load_from_str(content)
+    .ok()?
+    .into_iter()
+    .next()?;
+  Some(negatable_patterns(
+    "package.json",
+    workspace["packages"].as_vec()?,
+  ))
+}
+
+// Patterns starting with `!` leave out what they match
+fn negatable_patterns(manifest: &'static str, patterns: &[Yaml]) -> MemberPatterns {
+  let mut include: Vec<String> = vec![];
+  let mut exclude: Vec<String> = vec![];
+  for pattern in patterns.iter().filter_map(Yaml::as_str) {
+    match pattern.strip_prefix('!') {
+      Some(negated) => exclude.push(negated.to_string()),
+      None => include.push(pattern.to_string()),
+    }
+  }
+  MemberPatterns {
+    manifest,
+    include,
+    exclude,
+  }
+}
+
+fn pattern_components(pattern: &str) -> Vec<&str> {
+  pattern
+    .split('/')
+    .filter(|component| !component.is_empty() && *component != ".")
+    .collect()
+}
+
+// Directories matching the pattern components below base. `**` does not step into a
+// directory it is already in, as a symlink can lead back up the tree.
+fn expand<'a>(
+  base: PathBuf,
+  components: &'a [&'a str],
+  depth_left: usize,
+  ancestors: Vec<(u64, u64)>,
+) -> Pin<Box<dyn Future<Output = Vec<PathBuf>> + Send + 'a>> {
+  Box::pin(async move {
+    let Some((first, rest)) = components.split_first() else {
+      return vec![base];
+    };
+    if *first == "**" {
+      let mut matches = expand(base.clone(), rest, depth_left, ancestors.clone()).await;
+      if depth_left > 0 {
+        for child in child_dirs(&base).await {
+          let id = file_id(&child).await;
+          if id.is_some_and(|id| ancestors.contains(&id)) {
+            continue;
+          }
+          let mut child_ancestors = ancestors.clone();
+          child_ancestors.extend(id);
+          matches.extend(expand(child, components, depth_left - 1, child_ancestors).await);
+        }
+      }
+      return matches;
+    }
+    if first.contains(['*', '?']) {
+      let mut matches: Vec<PathBuf> = vec![];
+      for child in child_dirs(&base).await {
+        let name_matches = child
+          .file_name()
+          .is_some_and(|name| wildcard_matches(first, &name.to_string_lossy()));
+        if name_matches {
+          matches.extend(expand(child, rest, depth_left, ancestors.clone()).await);
+        }
+      }
+      return matches;
+    }
+    let child = base.join(first);
+    match is_dir(&child).await {
+      true => expand(child, rest, depth_left, ancestors).await,
+      false => vec![],
+    }
+  })
+}
+
+// Subdirectories a wildcard may step into, dependencies and hidden directories are never members
+async fn child_dirs(dir: &Path) -> Vec<PathBuf> {
+  let mut dirs: Vec<PathBuf> = vec![];
+  for (path, name) in read_entries(dir, &mut vec![]).await.unwrap_or_default() {
+    let name = name.to_string_lossy();
+    if !name.starts_with('.') && name != "node_modules" && name != "target" && is_dir(&path).await {
+      dirs.push(path);
+    }
+  }
+  dirs.sort();
+  dirs
+}
+
+// Whether a `/`-separated relative path matches the pattern components
+fn glob_matches(components: &[&str], path: &str) -> bool {
+  let parts = path.split('/').collect::<Vec<&str>>();
+  components_match(components, &parts)
+}
+
+fn components_match(components: &[&str], parts: &[&str]) -> bool {
+  match components.split_first() {
+    None => parts.is_empty(),
+    Some((&"**", rest)) => (0..=parts.len()).any(|skip| components_match(rest, &parts[skip..])),
+    Some((first, rest)) => match parts.split_first() {
+      Some((part, parts)) => wildcard_matches(first, part) && components_match(rest, parts),
+      None => false,
+    },
+  }
+}
+
+// `*` matches any run of characters and `?` a single one, within a single path component
+fn wildcard_matches(pattern: &str, name: &str) -> bool {
+  let pattern = pattern.chars().collect::<Vec<char>>();
+  let name = name.chars().collect::<Vec<char>>();
+  fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+      None => name.is_empty(),
+      Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+      Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+      Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+    }
+  }
+  matches(&pattern, &name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cargo_members() {
+    let members = cargo_members(
+      "[package]\nname = \"root\"\nmembers = [\"not-this\"]\n\n\
+       [workspace]\nresolver = \"2\"\nmembers = [\n  \"crates/*\", # all crates\n  \
+       \"tools/#cli\",\n  'lib#1', # \"not-this\"\n]\n\
+       exclude = [\"crates/legacy\"]\n\n[workspace.dependencies]\nserde = \"1\"\n",
+    )
+    .unwrap();
+    assert_eq!(members.include, vec!["crates/*", "tools/#cli", "lib#1"]);
+    assert_eq!(members.exclude, vec!["crates/legacy"]);
+    assert!(cargo_members("[package]\nname = \"single\"\n").is_none());
+  }
+
+  #[test]
+  fn test_npm_members() {
+    let members = npm_members(
+      "{\n\t\"name\": \"root\",\n\t\"workspaces\": [\"packages/*\", \"!packages/old\"]\n}",
+    )
+    .unwrap();
+    assert_eq!(members.include, vec!["packages/*"]);
+    assert_eq!(members.exclude, vec!["packages/old"]);
+    let members = npm_members(r#"{"workspaces": {"packages": ["apps/*"]}}"#).unwrap();
+    assert_eq!(members.include, vec!["apps/*"]);
+    assert!(npm_members(r#"{"name": "single"}"#).is_none());
+  }
+
+  #[test]
+  fn test_pnpm_members() {
+    let members = pnpm_members("packages:\n  - 'packages/**'\n  - '!**/test/**'\n").unwrap();
+    assert_eq!(members.include, vec!["packages/**"]);
+    assert_eq!(members.exclude, vec!["**/test/**"]);
+  }
+
+  #[test]
+  fn test_glob_matches() {
+    assert!(glob_matches(&["crates", "*"], "crates/foo"));
+    assert!(!glob_matches(&["crates", "*"], "crates/foo/bar"));
+    assert!(glob_matches(&["**", "test", "**"], "packages/a/test/b"));
+    assert!(glob_matches(&["**", "test", "**"], "test"));
+    assert!(glob_matches(&["pkg-?"], "pkg-a"));
+    assert!(!glob_matches(&["pkg-?"], "pkg-ab"));
+  }
+}
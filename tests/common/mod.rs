@@ -0,0 +1,10 @@
+use std::fs;
+use std::path::PathBuf;
+
+// Fixtures containing `.git` directories cannot be committed, so build them at runtime
+pub fn temp_fixture(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("popi-{}-{}", name, std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
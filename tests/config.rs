@@ -1,7 +1,9 @@
 extern crate popi;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use popi::config::{LoadConfigError, LocalStorage};
+use popi::config::{LoadConfigError, LocalStorage, RepoRoot, RootLayout, DEFAULT_SCAN_TIMEOUT};
+use popi::ghq::GhqEnvironment;
 
 #[test]
 fn loading_no_paths_config_file() {
@@ -19,11 +21,11 @@ fn loading_correct_paths() {
   let config = LocalStorage::new_from_root_path("tests/fixtures/config_1".into()).unwrap();
   assert_eq!(config.repo_paths.len(), 2);
   assert_eq!(
-    config.repo_paths[0].to_str().unwrap(),
+    config.repo_paths[0].path.to_str().unwrap(),
     "/Users/aspulse/repositories"
   );
   assert_eq!(
-    config.repo_paths[1].to_str().unwrap(),
+    config.repo_paths[1].path.to_str().unwrap(),
     "/Users/aspulse/github"
   );
 }
@@ -55,3 +57,201 @@ fn loading_config_with_no_paths() {
   let config = LocalStorage::new_from_root_path("tests/fixtures/config_4".into()).unwrap();
   assert_eq!(config.repo_paths.len(), 0);
 }
+
+#[test]
+fn loading_roots_with_layout() {
+  let config = LocalStorage::new_from_root_path("tests/fixtures/config_5".into()).unwrap();
+  assert_eq!(config.repo_paths.len(), 2);
+  assert_eq!(
+    config.repo_paths[0].path.to_str().unwrap(),
+    "/Users/aspulse/repositories"
+  );
+  assert_eq!(config.repo_paths[0].layout, RootLayout::Flat);
+  assert_eq!(
+    config.repo_paths[1].path.to_str().unwrap(),
+    "/Users/aspulse/ghq"
+  );
+  assert_eq!(config.repo_paths[1].layout, RootLayout::Ghq);
+}
+
+#[test]
+fn loading_unknown_layout() {
+  let err = LocalStorage::new_from_root_path("tests/fixtures/config_6".into()).unwrap_err();
+  assert_eq!(
+    err,
+    LoadConfigError::ConfigInvalidYamlFormat {
+      config_yml_path: "tests/fixtures/config_6/config.yml".to_string()
+    }
+  );
+}
+
+#[test]
+fn detecting_ghq_roots_without_config_file() {
+  let env = GhqEnvironment {
+    ghq_root: None,
+    home_dir: Some("tests/fixtures/ghq_detect_1/home".into()),
+    xdg_config_home: Some("tests/fixtures/ghq_detect_1/xdg".into()),
+  };
+  let config =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/ghq_detect_1/config".into(), &env)
+      .unwrap();
+  assert_eq!(
+    config.repo_paths,
+    vec![
+      RepoRoot::new(
+        "tests/fixtures/ghq_detect_1/home/xdg-ghq".into(),
+        RootLayout::Ghq
+      ),
+      RepoRoot::new(
+        "tests/fixtures/ghq_detect_1/home/src".into(),
+        RootLayout::Ghq
+      ),
+      RepoRoot::new("/opt/ghq".into(), RootLayout::Ghq),
+    ]
+  );
+}
+
+#[test]
+fn detecting_ghq_roots_from_env_variable() {
+  let env = GhqEnvironment {
+    ghq_root: Some("/srv/ghq-a:~/ghq-b".into()),
+    home_dir: Some("tests/fixtures/ghq_detect_1/home".into()),
+    xdg_config_home: None,
+  };
+  let config =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/ghq_detect_1/config".into(), &env)
+      .unwrap();
+  assert_eq!(
+    config.repo_paths,
+    vec![
+      RepoRoot::new("/srv/ghq-a".into(), RootLayout::Ghq),
+      RepoRoot::new(
+        "tests/fixtures/ghq_detect_1/home/ghq-b".into(),
+        RootLayout::Ghq
+      ),
+    ]
+  );
+}
+
+#[test]
+fn detecting_default_ghq_root_with_config_file() {
+  let env = GhqEnvironment {
+    ghq_root: None,
+    home_dir: Some("tests/fixtures/ghq_detect_2/home".into()),
+    xdg_config_home: None,
+  };
+  let config =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/ghq_detect_2/config".into(), &env)
+      .unwrap();
+  assert_eq!(
+    config.repo_paths,
+    vec![
+      RepoRoot::new("/Users/aspulse/repositories".into(), RootLayout::Flat),
+      RepoRoot::new(
+        "tests/fixtures/ghq_detect_2/home/ghq".into(),
+        RootLayout::Ghq
+      ),
+    ]
+  );
+}
+
+#[test]
+fn detecting_ghq_roots_only_without_repos() {
+  let env = GhqEnvironment {
+    ghq_root: Some("/srv/ghq".into()),
+    home_dir: None,
+    xdg_config_home: None,
+  };
+  let config =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/ghq_detect_4/config".into(), &env)
+      .unwrap();
+  assert_eq!(
+    config.repo_paths,
+    vec![RepoRoot::from(PathBuf::from("/Users/aspulse/repositories"))]
+  );
+  let config =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/ghq_detect_5/config".into(), &env)
+      .unwrap();
+  assert_eq!(
+    config.repo_paths,
+    vec![RepoRoot::new("/srv/ghq".into(), RootLayout::Ghq)]
+  );
+}
+
+#[test]
+fn detecting_ghq_roots_disabled() {
+  let env = GhqEnvironment {
+    ghq_root: Some("/srv/ghq".into()),
+    home_dir: None,
+    xdg_config_home: None,
+  };
+  let config =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/ghq_detect_3/config".into(), &env)
+      .unwrap();
+  assert_eq!(config.repo_paths.len(), 1);
+  assert_eq!(config.repo_paths[0].layout, RootLayout::Flat);
+}
+
+#[test]
+fn detecting_no_ghq_roots_without_config_file() {
+  let env = GhqEnvironment::default();
+  let err =
+    LocalStorage::new_with_ghq_detection("tests/fixtures/config_0".into(), &env).unwrap_err();
+  assert_eq!(
+    err,
+    LoadConfigError::NoConfigFileFound {
+      root_path: PathBuf::from("tests/fixtures/config_0")
+    }
+  );
+}
+
+#[test]
+fn loading_scan_timeouts() {
+  let config = LocalStorage::new_from_root_path("tests/fixtures/config_7".into()).unwrap();
+  let options = &config.finder_options;
+  assert_eq!(options.scan_timeout, Duration::from_millis(2500));
+  assert_eq!(config.repo_paths[0].timeout, None);
+  assert_eq!(
+    config.repo_paths[0].timeout(options),
+    Duration::from_millis(2500)
+  );
+  assert_eq!(config.repo_paths[1].timeout, Some(Duration::from_secs(30)));
+  assert_eq!(
+    config.repo_paths[1].timeout(options),
+    Duration::from_secs(30)
+  );
+
+  let config = LocalStorage::new_from_root_path("tests/fixtures/config_1".into()).unwrap();
+  assert_eq!(config.finder_options.scan_timeout, DEFAULT_SCAN_TIMEOUT);
+}
+
+#[test]
+fn loading_invalid_timeout() {
+  let err = LocalStorage::new_from_root_path("tests/fixtures/config_8".into()).unwrap_err();
+  assert_eq!(
+    err,
+    LoadConfigError::ConfigInvalidYamlFormat {
+      config_yml_path: "tests/fixtures/config_8/config.yml".to_string()
+    }
+  );
+}
+
+#[test]
+fn loading_listing_switches() {
+  let config = LocalStorage::new_from_root_path("tests/fixtures/config_9".into()).unwrap();
+  assert!(config.finder_options.show_hidden);
+  assert!(config.finder_options.recent_projects);
+  assert!(config.finder_options.workspaces);
+  assert!(config.finder_options.show_archived);
+  assert_eq!(
+    config.finder_options.archived,
+    vec![PathBuf::from("/Users/aspulse/repositories/old")]
+  );
+
+  let config = LocalStorage::new_from_root_path("tests/fixtures/config_1".into()).unwrap();
+  assert!(!config.finder_options.show_hidden);
+  assert!(!config.finder_options.recent_projects);
+  assert!(!config.finder_options.workspaces);
+  assert!(!config.finder_options.show_archived);
+  assert!(config.finder_options.archived.is_empty());
+}
@@ -0,0 +1,98 @@
+extern crate popi;
+use std::fs;
+
+use popi::config::RepoRoot;
+use popi::editors::{EditorEnvironment, JetBrainsSource, VsCodeSource};
+use popi::finder::{spawn_sources, ReposFinder, ReposUpdate};
+use popi::source::RepoSource;
+use tokio::sync::mpsc;
+
+mod common;
+use common::temp_fixture;
+
+#[tokio::test]
+async fn list_recent_projects_of_editors() {
+  let dir = temp_fixture("editors_1");
+  let home = dir.join("home");
+  let config_dir = dir.join("config");
+  fs::create_dir_all(home.join("root/popi")).unwrap();
+  fs::create_dir_all(home.join("scratch/my app")).unwrap();
+  fs::write(home.join("scratch/my app/package.json"), "{}").unwrap();
+  fs::create_dir_all(home.join("work/service")).unwrap();
+  fs::create_dir_all(home.join("notes")).unwrap();
+  fs::create_dir_all(home.join("IdeaProjects")).unwrap();
+
+  let storage = config_dir.join("Code/User/globalStorage");
+  fs::create_dir_all(&storage).unwrap();
+  let uri = |path: &str| format!("file://{}", home.join(path).display()).replace(' ', "%20");
+  fs::write(
+    storage.join("storage.json"),
+    format!(
+      "{{\n\t\"backupWorkspaces\": {{\"folders\": [{{\"folderUri\": \"{}\"}}]}},\n\t\
+       \"windowsState\": {{\"lastActiveWindow\": {{\"folder\": \"{}\"}}, \
+       \"openedWindows\": [{{\"folder\": \"{}\"}}]}},\n\t\
+       \"profileAssociations\": {{\"workspaces\": {{\"{}\": \"__default__profile__\"}}}}\n}}",
+      uri("scratch/my app"),
+      uri("root/popi"),
+      uri("deleted/long-ago"),
+      uri("notes"),
+    ),
+  )
+  .unwrap();
+
+  let options = config_dir.join("JetBrains/IntelliJIdea2024.1/options");
+  fs::create_dir_all(&options).unwrap();
+  fs::write(
+    options.join("recentProjects.xml"),
+    "<application>\n  <component name=\"RecentProjectsManager\">\n    \
+     <option name=\"additionalInfo\">\n      <map>\n        \
+     <entry key=\"$USER_HOME$/work/service\">\n        </entry>\n      \
+     </map>\n    </option>\n    \
+     <option name=\"lastProjectLocation\" value=\"$USER_HOME$/IdeaProjects\" />\n  \
+     </component>\n</application>\n",
+  )
+  .unwrap();
+
+  let env = EditorEnvironment {
+    config_dir: Some(config_dir.clone()),
+    home_dir: Some(home.clone()),
+  };
+  let sources: Vec<Box<dyn RepoSource>> = vec![
+    Box::new(VsCodeSource { env: env.clone() }),
+    Box::new(JetBrainsSource { env }),
+  ];
+  let (reposupdate_tx, mut reposupdate_rx) = mpsc::channel::<ReposUpdate>(20);
+  spawn_sources(sources, reposupdate_tx);
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(home.join("root"))]);
+  finder.init().await;
+  while let Some(update) = reposupdate_rx.recv().await {
+    finder.apply(update).await;
+  }
+
+  let mut repos = finder
+    .search_by("")
+    .into_iter()
+    .map(|found| (found.repo.display_name(), found.repo.source))
+    .collect::<Vec<(String, String)>>();
+  repos.sort();
+  // `popi` is in a root already, the deleted folder is gone, `notes` was never a recent folder
+  // and `IdeaProjects` is only where new JetBrains projects go
+  assert_eq!(
+    repos,
+    vec![
+      ("my app".to_string(), "vscode".to_string()),
+      ("popi".to_string(), "directory".to_string()),
+      ("service".to_string(), "jetbrains".to_string()),
+    ]
+  );
+  assert_eq!(
+    finder.search_by("type:node")[0].repo.path,
+    home.join("scratch/my app")
+  );
+
+  // Without the root, the entry from VS Code shows up instead
+  finder.remove_root(&home.join("root")).await;
+  assert_eq!(finder.search_by("popi")[0].repo.source, "vscode");
+  fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,6 +1,15 @@
 extern crate popi;
-use popi::config::LocalStorage;
-use popi::finder::ReposFinder;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use popi::config::{LocalStorage, RepoRoot};
+use popi::finder::{spawn_scan, Repo, RepoKind, RepoStatus, ReposFinder, ReposUpdate};
+use popi::source::{RepoSource, SourceFuture};
+use tokio::sync::mpsc;
+
+mod common;
+use common::temp_fixture;
 
 #[tokio::test]
 async fn listup_from_one_directory() {
@@ -72,3 +81,898 @@ async fn search_by_1() {
   assert_eq!(repos[2].repo.name, "banana");
   assert_eq!(repos[3].repo.name, "sapporo");
 }
+
+// Lowercasing `İ` adds a character and shrinks the Kelvin sign, positions still have to
+// point at the characters shown
+#[tokio::test]
+async fn match_positions_in_non_ascii_names() {
+  let dir = temp_fixture("non_ascii_1");
+  fs::create_dir_all(dir.join("\u{130}zmir-\u{212A}it")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.clone())]);
+  finder.init().await;
+  let found = finder.search_by("\u{130}zmir-kit");
+  assert_eq!(found.len(), 1);
+  let text = found[0].search_text();
+  let matched = found[0]
+    .matched_string
+    .matched_positions
+    .iter()
+    .map(|&position| text[position..].chars().next().unwrap())
+    .collect::<String>();
+  assert_eq!(matched, "\u{130}zmir-\u{212A}it");
+
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn listup_ghq_layout() {
+  let config = LocalStorage::new_from_root_path("tests/fixtures/ghq_1/config".into()).unwrap();
+  let mut finder: ReposFinder = ReposFinder::new(config.repo_paths);
+  let status = finder.init().await;
+  let mut repos = finder.listup_repos();
+  dbg!(&repos);
+  assert_eq!(status.paths_not_found.len(), 0);
+  repos.sort_by_key(|a| a.display_name());
+  assert_eq!(repos.len(), 3);
+  assert_eq!(repos[0].display_name(), "aspulse/dotfiles");
+  assert_eq!(repos[0].host.as_deref(), Some("github.com"));
+  assert_eq!(repos[1].display_name(), "aspulse/popi");
+  assert_eq!(repos[1].name, "popi");
+  assert_eq!(repos[2].display_name(), "someone/popi");
+  assert_eq!(repos[2].host.as_deref(), Some("gitlab.com"));
+  assert_eq!(repos[2].owner.as_deref(), Some("someone"));
+}
+
+#[tokio::test]
+async fn search_by_owner_in_ghq_layout() {
+  let config = LocalStorage::new_from_root_path("tests/fixtures/ghq_1/config".into()).unwrap();
+  let mut finder: ReposFinder = ReposFinder::new(config.repo_paths);
+  finder.init().await;
+
+  let repos = finder.search_by("aspulse/popi");
+  dbg!(&repos);
+  assert_eq!(repos[0].repo.display_name(), "aspulse/popi");
+  assert_eq!(repos[0].matched_string.distance, 0);
+}
+
+#[tokio::test]
+async fn listup_linked_worktrees() {
+  let dir = temp_fixture("worktree_1");
+  let root = dir.join("repos");
+  let admin_dir = root.join("popi/.git/worktrees/popi-feature");
+  let checkout = dir.join("elsewhere/popi-feature");
+  let detached_admin_dir = root.join("popi/.git/worktrees/popi-review");
+  let detached_checkout = root.join("popi-review");
+  fs::create_dir_all(&admin_dir).unwrap();
+  fs::create_dir_all(&checkout).unwrap();
+  fs::create_dir_all(&detached_admin_dir).unwrap();
+  fs::create_dir_all(&detached_checkout).unwrap();
+  fs::create_dir_all(root.join("other")).unwrap();
+  fs::write(
+    admin_dir.join("gitdir"),
+    format!("{}\n", checkout.join(".git").display()),
+  )
+  .unwrap();
+  fs::write(admin_dir.join("HEAD"), "ref: refs/heads/feature/search\n").unwrap();
+  fs::write(
+    detached_admin_dir.join("gitdir"),
+    format!("{}\n", detached_checkout.join(".git").display()),
+  )
+  .unwrap();
+  fs::write(
+    detached_admin_dir.join("HEAD"),
+    "3f2a9c1d0e8b7a6f5e4d3c2b1a09f8e7d6c5b4a3\n",
+  )
+  .unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(root.clone())]);
+  let status = finder.init().await;
+  let mut repos = finder.listup_repos();
+  dbg!(&repos);
+  assert_eq!(status.paths_not_found.len(), 0);
+  repos.sort_by_key(|a| a.display_name());
+  assert_eq!(repos.len(), 4);
+  assert_eq!(repos[0].display_name(), "other");
+  assert_eq!(repos[1].display_name(), "popi");
+  assert_eq!(repos[2].display_name(), "popi@3f2a9c1");
+  assert_eq!(repos[2].path, detached_checkout);
+  assert_eq!(repos[3].display_name(), "popi@feature/search");
+  assert_eq!(repos[3].path, checkout);
+  assert_eq!(
+    repos[3].kind,
+    RepoKind::Worktree {
+      main_path: root.join("popi"),
+      branch: Some("feature/search".to_string()),
+    }
+  );
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn listup_submodules() {
+  let config =
+    LocalStorage::new_from_root_path("tests/fixtures/submodule_1/config".into()).unwrap();
+  assert!(config.finder_options.submodules);
+  let mut finder = ReposFinder::new_with_options(config.repo_paths, config.finder_options);
+  finder.init().await;
+  let mut repos = finder.listup_repos();
+  dbg!(&repos);
+  repos.sort_by_key(|a| a.display_name());
+  assert_eq!(repos.len(), 5);
+  assert_eq!(repos[0].display_name(), "mono");
+  assert_eq!(repos[1].display_name(), "mono/libs/core");
+  assert_eq!(repos[1].depth(), 1);
+  assert_eq!(repos[2].display_name(), "mono/libs/core/deps/tiny");
+  assert_eq!(repos[2].depth(), 2);
+  assert_eq!(repos[3].display_name(), "mono/vendor/zlib");
+  assert_eq!(repos[3].depth(), 1);
+  assert_eq!(repos[4].display_name(), "other");
+}
+
+#[tokio::test]
+async fn skip_submodules_by_default() {
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(PathBuf::from(
+    "tests/fixtures/submodule_1/repos",
+  ))]);
+  finder.init().await;
+  assert_eq!(finder.listup_repos().len(), 2);
+}
+
+#[tokio::test]
+async fn listup_workspace_members() {
+  use popi::finder::RepoKind;
+
+  let config =
+    LocalStorage::new_from_root_path("tests/fixtures/workspace_1/config".into()).unwrap();
+  assert!(config.finder_options.workspaces);
+  let mut finder = ReposFinder::new_with_options(config.repo_paths, config.finder_options);
+  finder.init().await;
+  let names = finder
+    .search_by("")
+    .into_iter()
+    .map(|found| found.repo.display_name())
+    .collect::<Vec<String>>();
+  let mut sorted = names.clone();
+  sorted.sort();
+  assert_eq!(
+    sorted,
+    vec![
+      "cargo-mono",
+      "cargo-mono › crates/bar",
+      "cargo-mono › crates/foo",
+      "solo",
+      "web",
+      "web › apps/site",
+      "web › packages/ui",
+    ]
+  );
+  // Members follow their repository
+  let mono = names.iter().position(|name| name == "cargo-mono").unwrap();
+  assert_eq!(names[mono + 1], "cargo-mono › crates/bar");
+
+  let found = finder.search_by("cargo-mono › crates/foo");
+  let foo = found
+    .iter()
+    .find(|found| found.repo.display_name() == "cargo-mono › crates/foo")
+    .unwrap();
+  assert_eq!(
+    foo.repo.path,
+    PathBuf::from("tests/fixtures/workspace_1/repos/cargo-mono/crates/foo")
+  );
+  assert_eq!(foo.repo.depth(), 1);
+  assert!(matches!(foo.repo.kind, RepoKind::WorkspaceMember { .. }));
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(PathBuf::from(
+    "tests/fixtures/workspace_1/repos",
+  ))]);
+  finder.init().await;
+  assert_eq!(finder.listup_repos().len(), 3);
+}
+
+#[tokio::test]
+async fn search_submodules_under_parent() {
+  let config =
+    LocalStorage::new_from_root_path("tests/fixtures/submodule_1/config".into()).unwrap();
+  let mut finder = ReposFinder::new_with_options(config.repo_paths, config.finder_options);
+  finder.init().await;
+
+  let repos = finder.search_by("mono");
+  let names = repos
+    .iter()
+    .map(|found| found.repo.display_name())
+    .collect::<Vec<String>>();
+  dbg!(&names);
+  assert_eq!(
+    names[..4],
+    vec![
+      "mono",
+      "mono/libs/core",
+      "mono/libs/core/deps/tiny",
+      "mono/vendor/zlib",
+    ]
+  );
+
+  let repos = finder.search_by("mono/vendor/zlib");
+  let zlib = repos
+    .iter()
+    .find(|found| found.repo.display_name() == "mono/vendor/zlib")
+    .unwrap();
+  assert_eq!(zlib.matched_string.distance, 0);
+}
+
+#[tokio::test]
+async fn stream_roots_as_they_finish() {
+  let config =
+    LocalStorage::new_from_root_path("tests/fixtures/repo_search_3/config".into()).unwrap();
+  let (reposupdate_tx, mut reposupdate_rx) = mpsc::channel::<ReposUpdate>(20);
+  spawn_scan(config.repo_paths, config.finder_options, reposupdate_tx);
+
+  let mut finder = ReposFinder::new(vec![]);
+  finder.set_repos(vec![]).await;
+  let mut not_found: Vec<PathBuf> = vec![];
+  let mut scanned = 0;
+  // The channel closes once every root has been sent
+  while let Some(update) = reposupdate_rx.recv().await {
+    if let ReposUpdate::RootScanned {
+      status: RepoStatus::NotFound(path),
+      ..
+    } = &update
+    {
+      not_found.push(path.clone());
+    }
+    scanned += 1;
+    finder.apply(update).await;
+  }
+  assert_eq!(scanned, 2);
+  assert_eq!(
+    not_found,
+    vec![PathBuf::from("tests/fixtures/repo_search_3/repos-B")]
+  );
+  let mut repos = finder.listup_repos();
+  repos.sort_by(|a, b| a.name.cmp(&b.name));
+  assert_eq!(repos.len(), 2);
+  assert_eq!(repos[0].name, "a");
+  assert_eq!(
+    repos[0].root_path,
+    PathBuf::from("tests/fixtures/repo_search_3/repos-A")
+  );
+  assert_eq!(repos[1].name, "b");
+}
+
+#[tokio::test]
+async fn report_timed_out_roots() {
+  let dir = temp_fixture("timeout_1");
+  // Every entry is checked for worktrees, so this takes far longer than the timeout
+  for i in 0..2000 {
+    fs::create_dir_all(dir.join(format!("slow/repo-{}", i))).unwrap();
+  }
+  let slow_root = RepoRoot {
+    timeout: Some(Duration::from_millis(1)),
+    ..RepoRoot::from(dir.join("slow"))
+  };
+  let mut finder = ReposFinder::new(vec![
+    RepoRoot::from(PathBuf::from("tests/fixtures/repo_search_2/repos-A")),
+    slow_root,
+  ]);
+  let status = finder.init().await;
+  assert_eq!(status.paths_not_found.len(), 0);
+  assert_eq!(status.paths_timed_out, vec![dir.join("slow")]);
+  let mut repos = finder.listup_repos();
+  repos.sort_by(|a, b| a.name.cmp(&b.name));
+  assert_eq!(repos.len(), 2);
+  assert_eq!(repos[0].name, "a");
+  assert_eq!(repos[1].name, "b");
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn report_unreadable_directories() {
+  use std::os::unix::fs::{symlink, PermissionsExt};
+
+  let dir = temp_fixture("unreadable_1");
+  let root = dir.join("ghq");
+  fs::create_dir_all(root.join("github.com/aspulse/popi")).unwrap();
+  // A host replaced by a file and an owner pointing nowhere are skipped without a report
+  fs::write(root.join("example.com"), "").unwrap();
+  symlink(dir.join("gone"), root.join("github.com/gone")).unwrap();
+  // Can not be opened by anyone, unlike a directory without permissions
+  symlink(dir.join("loop-root"), dir.join("loop-root")).unwrap();
+  fs::create_dir_all(dir.join("locked-root")).unwrap();
+  fs::set_permissions(dir.join("locked-root"), fs::Permissions::from_mode(0o000)).unwrap();
+  // Permissions are not enforced for root
+  let permissions_enforced = fs::read_dir(dir.join("locked-root")).is_err();
+
+  let mut finder = ReposFinder::new(vec![
+    RepoRoot::new(root.clone(), popi::config::RootLayout::Ghq),
+    RepoRoot::from(dir.join("loop-root")),
+    RepoRoot::from(dir.join("locked-root")),
+  ]);
+  let status = finder.init().await;
+  assert_eq!(status.paths_not_found.len(), 0);
+  assert_eq!(
+    status
+      .io_errors
+      .iter()
+      .map(|error| error.path.clone())
+      .collect::<Vec<PathBuf>>(),
+    vec![dir.join("loop-root")]
+  );
+  assert_eq!(
+    status.paths_permission_denied,
+    match permissions_enforced {
+      true => vec![dir.join("locked-root")],
+      false => vec![],
+    }
+  );
+  let repos = finder.listup_repos();
+  assert_eq!(repos.len(), 1);
+  assert_eq!(repos[0].display_name(), "aspulse/popi");
+
+  fs::set_permissions(dir.join("locked-root"), fs::Permissions::from_mode(0o755)).unwrap();
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn listup_non_utf8_names() {
+  use std::ffi::OsStr;
+  use std::os::unix::ffi::OsStrExt;
+
+  let dir = temp_fixture("non_utf8_1");
+  let name = OsStr::from_bytes(b"caf\xe9");
+  fs::create_dir_all(dir.join("plain")).unwrap();
+  // Some file systems (e.g. APFS) only accept UTF-8 names, nothing to prove there
+  if fs::create_dir(dir.join(name)).is_err() {
+    fs::remove_dir_all(&dir).unwrap();
+    return;
+  }
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.clone())]);
+  let status = finder.init().await;
+  assert_eq!(status.io_errors.len(), 0);
+  let mut repos = finder.listup_repos();
+  repos.sort_by(|a, b| a.name.cmp(&b.name));
+  assert_eq!(repos.len(), 2);
+  assert_eq!(repos[0].name, name);
+  assert_eq!(repos[0].path, dir.join(name));
+  assert_eq!(repos[0].display_name(), "caf\u{FFFD}");
+
+  let found = finder.search_by("caf");
+  assert_eq!(found[0].repo.path, dir.join(name));
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn disambiguate_same_names_across_roots() {
+  let dir = temp_fixture("disambiguate_1");
+  fs::create_dir_all(dir.join("work/api")).unwrap();
+  fs::create_dir_all(dir.join("work/web")).unwrap();
+  fs::create_dir_all(dir.join("personal/api")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![
+    RepoRoot::from(dir.join("work")),
+    RepoRoot::from(dir.join("personal")),
+  ]);
+  finder.init().await;
+
+  let mut found = finder.search_by("api");
+  found.sort_by_key(|found| found.search_text());
+  let texts = found
+    .iter()
+    .map(|found| found.search_text())
+    .collect::<Vec<String>>();
+  assert_eq!(texts, vec!["api personal", "api work"]);
+  assert_eq!(finder.search_by("web")[0].suffix, None);
+
+  // The suffix can be matched as part of the query
+  let found = finder.search_by("api work");
+  assert_eq!(found[0].repo.path, dir.join("work/api"));
+  assert_eq!(found[0].matched_string.distance, 0);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rank_by_recent_activity() {
+  use popi::git_info::RepoDetails;
+
+  let dir = temp_fixture("activity_1");
+  for name in ["api-old", "api-new", "web"] {
+    fs::create_dir_all(dir.join(name)).unwrap();
+  }
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.clone())]);
+  finder.init().await;
+  finder.set_details(
+    [
+      ("api-old", 1600000000),
+      ("api-new", 1700000000),
+      ("web", 1650000000),
+    ]
+    .into_iter()
+    .map(|(name, last_active)| RepoDetails {
+      path: dir.join(name),
+      git: None,
+      last_active: Some(last_active),
+    })
+    .collect(),
+  );
+
+  let names = |found: Vec<popi::finder::FoundRepo>| {
+    found
+      .into_iter()
+      .map(|found| found.repo.display_name())
+      .collect::<Vec<String>>()
+  };
+  // Equally good matches are ordered by activity
+  assert_eq!(names(finder.search_by("api")), vec!["api-new", "api-old"]);
+  // Without a query everything is listed, most recent first
+  assert_eq!(
+    names(finder.search_by("")),
+    vec!["api-new", "web", "api-old"]
+  );
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn filter_by_project_type() {
+  use popi::project_type::ProjectType;
+
+  let dir = temp_fixture("project_types_1");
+  fs::create_dir_all(dir.join("popi")).unwrap();
+  fs::write(dir.join("popi/Cargo.toml"), "").unwrap();
+  fs::write(dir.join("popi/flake.nix"), "").unwrap();
+  fs::create_dir_all(dir.join("portal")).unwrap();
+  fs::write(dir.join("portal/package.json"), "{}").unwrap();
+  fs::create_dir_all(dir.join("notes")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.clone())]);
+  finder.init().await;
+  let mut repos = finder.listup_repos();
+  repos.sort_by(|a, b| a.name.cmp(&b.name));
+  assert_eq!(repos[0].project_types, vec![]);
+  assert_eq!(
+    repos[1].project_types,
+    vec![ProjectType::Rust, ProjectType::Nix]
+  );
+  assert_eq!(repos[2].project_types, vec![ProjectType::Node]);
+
+  let names = |keyword: &str| {
+    finder
+      .search_by(keyword)
+      .into_iter()
+      .map(|found| found.repo.display_name())
+      .collect::<Vec<String>>()
+  };
+  assert_eq!(names("po type:rust"), vec!["popi"]);
+  assert_eq!(names("type:js"), vec!["portal"]);
+  assert_eq!(names("type:rs type:nix"), vec!["popi"]);
+  assert_eq!(names("type:go"), Vec::<String>::new());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn filter_by_origin_host_and_owner() {
+  use popi::git_info::{GitInfo, RepoDetails};
+  use popi::remote::Remote;
+
+  let dir = temp_fixture("origin_1");
+  let root = dir.join("ghq");
+  fs::create_dir_all(root.join("github.com/aspulse/popi")).unwrap();
+  fs::create_dir_all(dir.join("flat/popi-fork")).unwrap();
+  fs::create_dir_all(dir.join("flat/tool")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![
+    RepoRoot::new(root.clone(), popi::config::RootLayout::Ghq),
+    RepoRoot::from(dir.join("flat")),
+  ]);
+  finder.init().await;
+  finder.set_details(vec![
+    RepoDetails {
+      path: dir.join("flat/popi-fork"),
+      git: Some(GitInfo {
+        origin: Remote::parse("git@github.com:someone/popi.git"),
+        ..Default::default()
+      }),
+      last_active: None,
+    },
+    RepoDetails {
+      path: dir.join("flat/tool"),
+      git: Some(GitInfo {
+        origin: Remote::parse("https://gitlab.com/team/tool"),
+        ..Default::default()
+      }),
+      last_active: None,
+    },
+  ]);
+
+  let mut repos = finder.listup_repos();
+  repos.sort_by(|a, b| a.path.cmp(&b.path));
+  // Only shown when the directory layout does not tell already
+  assert_eq!(
+    repos
+      .iter()
+      .map(|repo| repo.origin_location())
+      .collect::<Vec<Option<String>>>(),
+    vec![
+      Some("github.com/someone".to_string()),
+      Some("gitlab.com/team".to_string()),
+      None
+    ]
+  );
+
+  let paths = |keyword: &str| {
+    let mut paths = finder
+      .search_by(keyword)
+      .into_iter()
+      .map(|found| found.repo.path)
+      .collect::<Vec<PathBuf>>();
+    paths.sort();
+    paths
+  };
+  assert_eq!(
+    paths("popi host:github"),
+    vec![
+      dir.join("flat/popi-fork"),
+      root.join("github.com/aspulse/popi")
+    ]
+  );
+  assert_eq!(
+    paths("popi owner:aspulse"),
+    vec![root.join("github.com/aspulse/popi")]
+  );
+  assert_eq!(paths("host:gitlab.com"), vec![dir.join("flat/tool")]);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn hide_dot_directories_by_default() {
+  let dir = temp_fixture("hidden_1");
+  let root = dir.join("ghq");
+  fs::create_dir_all(dir.join("flat/popi")).unwrap();
+  fs::create_dir_all(dir.join("flat/.cache")).unwrap();
+  fs::create_dir_all(root.join("github.com/.archive/popi-old")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![
+    RepoRoot::from(dir.join("flat")),
+    RepoRoot::new(root.clone(), popi::config::RootLayout::Ghq),
+  ]);
+  finder.init().await;
+  // Hidden entries are still found, only left out of the results
+  assert_eq!(finder.listup_repos().len(), 3);
+
+  let names = |finder: &ReposFinder| {
+    let mut names = finder
+      .search_by("")
+      .into_iter()
+      .map(|found| found.repo.display_name())
+      .collect::<Vec<String>>();
+    names.sort();
+    names
+  };
+  assert_eq!(names(&finder), vec!["popi"]);
+  finder.set_show_hidden(true);
+  assert_eq!(names(&finder), vec![".archive/popi-old", ".cache", "popi"]);
+  finder.set_show_hidden(false);
+  assert_eq!(finder.search_by("cache").len(), 0);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn hide_worktrees_by_their_own_path() {
+  let dir = temp_fixture("hidden_2");
+  let root = dir.join("repos");
+  let worktree = |main: &str, id: &str, checkout: &Path| {
+    let admin_dir = root.join(main).join(".git/worktrees").join(id);
+    fs::create_dir_all(&admin_dir).unwrap();
+    fs::create_dir_all(checkout).unwrap();
+    fs::write(
+      admin_dir.join("gitdir"),
+      format!("{}\n", checkout.join(".git").display()),
+    )
+    .unwrap();
+    fs::write(admin_dir.join("HEAD"), format!("ref: refs/heads/{}\n", id)).unwrap();
+  };
+  worktree(".dotfiles", "work", &dir.join("elsewhere/dotfiles-work"));
+  worktree("popi", "tmp", &dir.join("elsewhere/.popi-tmp"));
+  worktree("popi", "feature", &root.join(".worktrees/popi-feature"));
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(root.clone())]);
+  finder.init().await;
+  let mut names = finder
+    .search_by("")
+    .into_iter()
+    .map(|found| found.repo.display_name())
+    .collect::<Vec<String>>();
+  names.sort();
+  // Outside the root only the checkout's own name counts, inside it the path under the root
+  assert_eq!(names, vec![".dotfiles@work", "popi"]);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn leave_out_archived_repos() {
+  let dir = temp_fixture("archived_1");
+  fs::create_dir_all(dir.join("popi")).unwrap();
+  fs::create_dir_all(dir.join("popi-v1")).unwrap();
+  fs::write(dir.join("popi-v1/.popi-archived"), "").unwrap();
+  fs::create_dir_all(dir.join("popi-v2/.git")).unwrap();
+  fs::write(
+    dir.join("popi-v2/.git/config"),
+    "[core]\n\tbare = false\n[popi]\n\tarchived = true\n",
+  )
+  .unwrap();
+  fs::create_dir_all(dir.join("popi-v3")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.clone())]);
+  finder.options.archived = vec![dir.join("popi-v3")];
+  finder.init().await;
+
+  let names = |finder: &ReposFinder, keyword: &str| {
+    let mut names = finder
+      .search_by(keyword)
+      .into_iter()
+      .map(|found| (found.repo.display_name(), found.repo.archived))
+      .collect::<Vec<(String, bool)>>();
+    names.sort();
+    names
+  };
+  assert_eq!(names(&finder, "popi"), vec![("popi".to_string(), false)]);
+  assert_eq!(
+    names(&finder, "is:archived popi"),
+    vec![
+      ("popi-v1".to_string(), true),
+      ("popi-v2".to_string(), true),
+      ("popi-v3".to_string(), true),
+    ]
+  );
+  finder.set_show_archived(true);
+  assert_eq!(names(&finder, "").len(), 4);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn add_remove_and_refresh_roots() {
+  let dir = temp_fixture("roots_1");
+  fs::create_dir_all(dir.join("work/api")).unwrap();
+  fs::create_dir_all(dir.join("personal/notes")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.join("work"))]);
+  finder.init().await;
+  assert_eq!(finder.listup_repos().len(), 1);
+
+  let delta = finder.add_root(RepoRoot::from(dir.join("personal"))).await;
+  assert_eq!(delta.root_path, dir.join("personal"));
+  assert_eq!(
+    delta
+      .added
+      .iter()
+      .map(|repo| repo.path.clone())
+      .collect::<Vec<PathBuf>>(),
+    vec![dir.join("personal/notes")]
+  );
+  assert!(delta.removed.is_empty());
+  assert_eq!(finder.repo_paths.len(), 2);
+  assert_eq!(finder.listup_repos().len(), 2);
+
+  // Only the refreshed root is scanned again
+  fs::create_dir_all(dir.join("work/web")).unwrap();
+  fs::remove_dir_all(dir.join("work/api")).unwrap();
+  fs::create_dir_all(dir.join("personal/blog")).unwrap();
+  let delta = finder.refresh(&dir.join("work")).await.unwrap();
+  assert_eq!(delta.added[0].path, dir.join("work/web"));
+  assert_eq!(delta.removed[0].path, dir.join("work/api"));
+  assert_eq!(finder.listup_repos().len(), 2);
+
+  let delta = finder.remove_root(&dir.join("personal")).await.unwrap();
+  assert_eq!(delta.removed[0].path, dir.join("personal/notes"));
+  assert!(delta.added.is_empty());
+  assert_eq!(finder.repo_paths.len(), 1);
+  assert_eq!(finder.listup_repos()[0].path, dir.join("work/web"));
+  assert!(finder.remove_root(&dir.join("personal")).await.is_none());
+  assert!(finder.refresh(&dir.join("personal")).await.is_none());
+
+  // Refreshing a root that disappeared reports it and drops its entries
+  fs::remove_dir_all(dir.join("work")).unwrap();
+  let delta = finder.refresh(&dir.join("work")).await.unwrap();
+  assert_eq!(delta.status.paths_not_found, vec![dir.join("work")]);
+  assert_eq!(delta.removed.len(), 1);
+  assert!(finder.listup_repos().is_empty());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+// Lists the directories named in a plain-text file, one path per line
+struct ListFileSource {
+  list_path: PathBuf,
+}
+
+impl RepoSource for ListFileSource {
+  fn name(&self) -> &str {
+    "list-file"
+  }
+
+  fn list(&self) -> SourceFuture<'_> {
+    Box::pin(async move {
+      let Ok(content) = fs::read_to_string(&self.list_path) else {
+        return RepoStatus::NotFound(self.list_path.clone());
+      };
+      let repos = content
+        .lines()
+        .map(PathBuf::from)
+        .map(|path| {
+          let name = path.file_name().unwrap().to_os_string();
+          Repo::new(path, name)
+        })
+        .collect();
+      RepoStatus::Found(repos, vec![])
+    })
+  }
+}
+
+#[tokio::test]
+async fn merge_repos_from_other_sources() {
+  use popi::source::DIRECTORY_SOURCE;
+
+  let dir = temp_fixture("sources_1");
+  fs::create_dir_all(dir.join("root/popi")).unwrap();
+  fs::create_dir_all(dir.join("elsewhere/dotfiles")).unwrap();
+  let list_path = dir.join("list.txt");
+  fs::write(
+    &list_path,
+    format!("{}\n", dir.join("elsewhere/dotfiles").display()),
+  )
+  .unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.join("root"))]);
+  finder.add_source(ListFileSource {
+    list_path: list_path.clone(),
+  });
+  finder.init().await;
+  let mut repos = finder.listup_repos();
+  repos.sort_by(|a, b| a.name.cmp(&b.name));
+  assert_eq!(
+    repos
+      .iter()
+      .map(|repo| (repo.display_name(), repo.source.as_str()))
+      .collect::<Vec<(String, &str)>>(),
+    vec![
+      ("dotfiles".to_string(), "list-file"),
+      ("popi".to_string(), DIRECTORY_SOURCE)
+    ]
+  );
+  assert_eq!(finder.search_by("dot")[0].repo.source, "list-file");
+
+  // Rescanning the root leaves entries of other sources alone
+  fs::create_dir_all(dir.join("root/web")).unwrap();
+  finder.refresh(&dir.join("root")).await.unwrap();
+  assert_eq!(finder.listup_repos().len(), 3);
+
+  fs::remove_file(&list_path).unwrap();
+  let status = finder.init().await;
+  assert_eq!(status.paths_not_found, vec![list_path]);
+  assert_eq!(finder.listup_repos().len(), 2);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn dedupe_symlinked_roots_and_entries() {
+  use std::os::unix::fs::symlink;
+
+  let dir = temp_fixture("symlinks_1");
+  fs::create_dir_all(dir.join("real/popi")).unwrap();
+  fs::create_dir_all(dir.join("real/web")).unwrap();
+  fs::create_dir_all(dir.join("other")).unwrap();
+  symlink(dir.join("real"), dir.join("alias")).unwrap();
+  symlink(dir.join("real/popi"), dir.join("other/popi-link")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![
+    RepoRoot::from(dir.join("real")),
+    RepoRoot::from(dir.join("alias")),
+    RepoRoot::from(dir.join("other")),
+  ]);
+  let status = finder.init().await;
+  let duplicates = status
+    .duplicates
+    .iter()
+    .map(|duplicate| (duplicate.path.clone(), duplicate.same_as.clone()))
+    .collect::<Vec<(PathBuf, PathBuf)>>();
+  assert_eq!(
+    duplicates,
+    vec![
+      (dir.join("alias"), dir.join("real")),
+      (dir.join("other/popi-link"), dir.join("real/popi")),
+    ]
+  );
+  let mut paths = finder
+    .listup_repos()
+    .into_iter()
+    .map(|repo| repo.path)
+    .collect::<Vec<PathBuf>>();
+  paths.sort();
+  assert_eq!(paths, vec![dir.join("real/popi"), dir.join("real/web")]);
+  assert_eq!(finder.duplicates().len(), 1);
+
+  // Roots added later are checked against the configured ones the same way
+  symlink(dir.join("real"), dir.join("alias-2")).unwrap();
+  let delta = finder.add_root(RepoRoot::from(dir.join("alias-2"))).await;
+  assert_eq!(delta.added.len(), 0);
+  assert_eq!(delta.status.duplicates.len(), 1);
+  assert_eq!(delta.status.duplicates[0].same_as, dir.join("real"));
+  assert_eq!(finder.listup_repos().len(), 2);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn stop_at_symlink_cycles() {
+  use popi::config::FinderOptions;
+  use std::os::unix::fs::symlink;
+
+  let dir = temp_fixture("symlinks_2");
+  // A submodule pointing back at its superproject
+  fs::create_dir_all(dir.join("mono/libs/core")).unwrap();
+  fs::write(
+    dir.join("mono/.gitmodules"),
+    "[submodule \"core\"]\n\tpath = libs/core\n[submodule \"self\"]\n\tpath = libs/self\n",
+  )
+  .unwrap();
+  symlink(dir.join("mono"), dir.join("mono/libs/self")).unwrap();
+  // A workspace whose `**` could walk in circles
+  fs::create_dir_all(dir.join("web/packages/ui")).unwrap();
+  fs::write(dir.join("web/package.json"), "{}").unwrap();
+  fs::write(dir.join("web/packages/ui/package.json"), "{}").unwrap();
+  fs::write(
+    dir.join("web/pnpm-workspace.yaml"),
+    "packages:\n  - 'packages/**'\n",
+  )
+  .unwrap();
+  symlink(dir.join("web/packages"), dir.join("web/packages/ui/back")).unwrap();
+
+  let mut finder = ReposFinder::new_with_options(
+    vec![RepoRoot::from(dir.clone())],
+    FinderOptions {
+      submodules: true,
+      workspaces: true,
+      ..Default::default()
+    },
+  );
+  finder.init().await;
+  let mut names = finder
+    .listup_repos()
+    .into_iter()
+    .map(|repo| repo.display_name())
+    .collect::<Vec<String>>();
+  names.sort();
+  assert_eq!(
+    names,
+    vec!["mono", "mono/libs/core", "web", "web › packages/ui"]
+  );
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn skip_ghq_directories_linking_back() {
+  use std::os::unix::fs::symlink;
+
+  let dir = temp_fixture("symlinks_3");
+  let root = dir.join("ghq");
+  fs::create_dir_all(root.join("github.com/aspulse/popi")).unwrap();
+  // An owner leading back to its host, and a repository leading back to the root
+  symlink(root.join("github.com"), root.join("github.com/mirror")).unwrap();
+  symlink(&root, root.join("github.com/aspulse/all")).unwrap();
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::new(
+    root.clone(),
+    popi::config::RootLayout::Ghq,
+  )]);
+  finder.init().await;
+  let paths = finder
+    .listup_repos()
+    .into_iter()
+    .map(|repo| repo.path)
+    .collect::<Vec<PathBuf>>();
+  assert_eq!(paths, vec![root.join("github.com/aspulse/popi")]);
+  fs::remove_dir_all(&dir).unwrap();
+}
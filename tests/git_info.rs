@@ -0,0 +1,246 @@
+extern crate popi;
+use std::fs;
+
+use popi::git_info::GitInfo;
+
+mod common;
+use common::temp_fixture;
+
+const COMMIT: &str = "0eb91449160daf68faf1741c416650b99d26b6b5";
+
+#[test]
+fn read_branch_from_packed_refs_and_worktrees() {
+  let dir = temp_fixture("git_info_1");
+  let git_dir = dir.join("popi/.git");
+  fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+  fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+  fs::write(
+    git_dir.join("config"),
+    "[remote \"origin\"]\n\turl = git@github.com:aspulse/popi.git\n",
+  )
+  .unwrap();
+  fs::write(
+    git_dir.join("packed-refs"),
+    format!("# pack-refs with: peeled\n{} refs/heads/main\n", COMMIT),
+  )
+  .unwrap();
+
+  // A linked worktree points at its admin directory, refs live in the main repository
+  let admin_dir = git_dir.join("worktrees/popi-feature");
+  fs::create_dir_all(&admin_dir).unwrap();
+  fs::create_dir_all(git_dir.join("refs/heads/topic")).unwrap();
+  fs::write(git_dir.join("refs/heads/topic/feature"), "abcdef0123\n").unwrap();
+  fs::write(admin_dir.join("HEAD"), "ref: refs/heads/topic/feature\n").unwrap();
+  fs::write(admin_dir.join("commondir"), "../..\n").unwrap();
+  fs::create_dir_all(dir.join("popi-feature")).unwrap();
+  fs::write(
+    dir.join("popi-feature/.git"),
+    format!("gitdir: {}\n", admin_dir.to_string_lossy()),
+  )
+  .unwrap();
+
+  // A detached HEAD has no branch, only the commit
+  fs::create_dir_all(dir.join("detached/.git")).unwrap();
+  fs::write(dir.join("detached/.git/HEAD"), format!("{}\n", COMMIT)).unwrap();
+
+  let info = GitInfo::read(&dir.join("popi")).unwrap();
+  assert_eq!(info.branch.as_deref(), Some("main"));
+  assert_eq!(info.commit.as_deref(), Some(COMMIT));
+  assert!(!info.dirty);
+  assert_eq!(info.label().as_deref(), Some("main"));
+
+  let info = GitInfo::read(&dir.join("popi-feature")).unwrap();
+  assert_eq!(info.branch.as_deref(), Some("topic/feature"));
+  // Worktrees share the config of the main repository
+  assert_eq!(
+    info.origin.map(|origin| origin.location()),
+    Some("github.com/aspulse".to_string())
+  );
+  assert_eq!(info.commit.as_deref(), Some("abcdef0123"));
+
+  let info = GitInfo::read(&dir.join("detached")).unwrap();
+  assert_eq!(info.branch, None);
+  assert_eq!(info.label().as_deref(), Some("0eb9144"));
+
+  assert_eq!(GitInfo::read(&dir.join("missing")), None);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn read_last_active_from_reflog_or_commit() {
+  use flate2::{write::ZlibEncoder, Compression};
+  use popi::git_info::RepoDetails;
+  use std::io::Write;
+
+  let dir = temp_fixture("git_info_2");
+  let with_reflog = dir.join("with-reflog/.git");
+  fs::create_dir_all(with_reflog.join("logs")).unwrap();
+  fs::write(with_reflog.join("HEAD"), format!("{}\n", COMMIT)).unwrap();
+  fs::write(
+    with_reflog.join("logs/HEAD"),
+    format!(
+      "0000000000000000000000000000000000000000 {0} A <a@example.com> 1700000000 +0900\tclone\n\
+       {0} {0} A <a@example.com> 1700000500 +0900\tcheckout: moving from main to main\n",
+      COMMIT
+    ),
+  )
+  .unwrap();
+
+  // Without a reflog the committer time of the loose commit object is used
+  let with_commit = dir.join("with-commit/.git");
+  let object_dir = with_commit.join("objects").join(&COMMIT[..2]);
+  fs::create_dir_all(&object_dir).unwrap();
+  fs::write(with_commit.join("HEAD"), format!("{}\n", COMMIT)).unwrap();
+  let body = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+    author A <a@example.com> 1600000000 +0000\n\
+    committer A <a@example.com> 1650000000 +0000\n\nmessage\n";
+  let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+  write!(encoder, "commit {}\0{}", body.len(), body).unwrap();
+  fs::write(object_dir.join(&COMMIT[2..]), encoder.finish().unwrap()).unwrap();
+
+  fs::create_dir_all(dir.join("plain")).unwrap();
+
+  assert_eq!(
+    RepoDetails::read(&dir.join("with-reflog")).last_active,
+    Some(1700000500)
+  );
+  assert_eq!(
+    RepoDetails::read(&dir.join("with-commit")).last_active,
+    Some(1650000000)
+  );
+  // Not a git repository, the directory mtime is all there is
+  let details = RepoDetails::read(&dir.join("plain"));
+  assert_eq!(details.git, None);
+  assert!(details.last_active.is_some());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn count_ahead_behind_and_stashes() {
+  use flate2::{write::ZlibEncoder, Compression};
+  use popi::git_info::GitCounts;
+  use std::io::Write;
+
+  let dir = temp_fixture("git_info_3");
+  let git_dir = dir.join("popi/.git");
+  // Object ids are never verified, any unique hex string will do
+  let write_commit = |id: &str, parents: &[&str], time: i64| {
+    let mut body = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n".to_string();
+    for parent in parents {
+      body.push_str(&format!("parent {}\n", parent));
+    }
+    body.push_str(&format!(
+      "committer A <a@example.com> {} +0000\n\nm\n",
+      time
+    ));
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    write!(encoder, "commit {}\0{}", body.len(), body).unwrap();
+    let object_dir = git_dir.join("objects").join(&id[..2]);
+    fs::create_dir_all(&object_dir).unwrap();
+    fs::write(object_dir.join(&id[2..]), encoder.finish().unwrap()).unwrap();
+  };
+  let id = |n: char| n.to_string().repeat(40);
+  write_commit(&id('1'), &[], 1000);
+  write_commit(&id('2'), &[&id('1')], 2000);
+  write_commit(&id('3'), &[&id('2')], 3000);
+  write_commit(&id('4'), &[&id('1')], 2500);
+
+  fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+  fs::create_dir_all(git_dir.join("refs/remotes/origin")).unwrap();
+  fs::create_dir_all(git_dir.join("logs/refs")).unwrap();
+  fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+  fs::write(git_dir.join("refs/heads/main"), id('3')).unwrap();
+  fs::write(git_dir.join("refs/remotes/origin/main"), id('4')).unwrap();
+  fs::write(
+    git_dir.join("config"),
+    "[branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n",
+  )
+  .unwrap();
+  fs::write(
+    git_dir.join("logs/refs/stash"),
+    format!(
+      "{0} {1} A <a@example.com> 1 +0000\tWIP on main\n{1} {2} A <a@example.com> 2 +0000\tWIP\n",
+      id('0'),
+      id('5'),
+      id('6')
+    ),
+  )
+  .unwrap();
+
+  let counts = GitCounts::read(&dir.join("popi")).unwrap();
+  assert_eq!(counts.ahead_behind, Some((2, 1)));
+  assert_eq!(counts.stashes, 2);
+  assert_eq!(counts.label().as_deref(), Some("↑2 ↓1 ≡2"));
+
+  // Without an upstream only the stashes are known
+  fs::write(git_dir.join("config"), "").unwrap();
+  let counts = GitCounts::read(&dir.join("popi")).unwrap();
+  assert_eq!(counts.ahead_behind, None);
+  assert_eq!(counts.label().as_deref(), Some("≡2"));
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn report_staged_changes_as_dirty() {
+  use flate2::{write::ZlibEncoder, Compression};
+  use std::io::Write;
+
+  let dir = temp_fixture("git_info_4");
+  let git_dir = dir.join("popi/.git");
+  let write_object = |id: &str, kind: &str, body: &[u8]| {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    write!(encoder, "{} {}\0", kind, body.len()).unwrap();
+    encoder.write_all(body).unwrap();
+    let object_dir = git_dir.join("objects").join(&id[..2]);
+    fs::create_dir_all(&object_dir).unwrap();
+    fs::write(object_dir.join(&id[2..]), encoder.finish().unwrap()).unwrap();
+  };
+  let tree_id = "1".repeat(40);
+  let mut tree = b"100644 README.md\0".to_vec();
+  tree.extend_from_slice(&[0xaa; 20]);
+  write_object(&tree_id, "tree", &tree);
+  write_object(
+    COMMIT,
+    "commit",
+    format!(
+      "tree {}\ncommitter A <a@example.com> 1 +0000\n\nm\n",
+      tree_id
+    )
+    .as_bytes(),
+  );
+  fs::write(git_dir.join("HEAD"), format!("{}\n", COMMIT)).unwrap();
+
+  // One assume-unchanged entry, so the work tree never makes it dirty
+  let write_index = |blob: u8, cache_tree: Option<&str>| {
+    let mut index = b"DIRC\0\0\0\x02\0\0\0\x01".to_vec();
+    index.extend_from_slice(&[0; 24]);
+    index.extend_from_slice(&0o100644u32.to_be_bytes());
+    index.extend_from_slice(&[0; 12]);
+    index.extend_from_slice(&[blob; 20]);
+    index.extend_from_slice(&(0x8000u16 | 9).to_be_bytes());
+    // 62 bytes before the path, the NUL after it pads the entry to 72
+    index.extend_from_slice(b"README.md\0");
+    if let Some(cache_tree) = cache_tree {
+      let mut data = b"\x001 0\n".to_vec();
+      data.extend((0..20).map(|i| u8::from_str_radix(&cache_tree[i * 2..i * 2 + 2], 16).unwrap()));
+      index.extend_from_slice(b"TREE");
+      index.extend_from_slice(&(data.len() as u32).to_be_bytes());
+      index.extend_from_slice(&data);
+    }
+    index.extend_from_slice(&[0; 20]);
+    fs::write(git_dir.join("index"), index).unwrap();
+  };
+  let dirty = || GitInfo::read(&dir.join("popi")).unwrap().dirty;
+
+  write_index(0xaa, None);
+  assert!(!dirty());
+  // Staged but not committed, the work tree matches the index
+  write_index(0xbb, None);
+  assert!(dirty());
+  // The cache tree answers without reading the tree
+  write_index(0xbb, Some(&tree_id));
+  assert!(!dirty());
+  write_index(0xaa, Some(&"2".repeat(40)));
+  assert!(dirty());
+  fs::remove_dir_all(&dir).unwrap();
+}
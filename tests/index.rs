@@ -0,0 +1,273 @@
+extern crate popi;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use popi::config::{FinderOptions, RepoRoot, RootLayout};
+use popi::finder::{RepoStatus, ReposFinder, ReposUpdate, ScanError, ScanErrorKind};
+use popi::index::RepoIndex;
+use tokio::sync::mpsc;
+
+mod common;
+use common::temp_fixture;
+
+// Move the mtime forward explicitly, filesystems may not tick between two quick writes
+fn touch_dir(path: &PathBuf) {
+  fs::File::open(path)
+    .unwrap()
+    .set_modified(SystemTime::now() + Duration::from_secs(60))
+    .unwrap();
+}
+
+#[tokio::test]
+async fn save_and_load_index() {
+  let dir = temp_fixture("index_1");
+  let root = dir.join("repos");
+  fs::create_dir_all(root.join("a")).unwrap();
+  fs::write(root.join("a/Cargo.toml"), "").unwrap();
+  fs::create_dir_all(root.join("b/.git/worktrees/b-feature")).unwrap();
+  fs::create_dir_all(dir.join("b-feature")).unwrap();
+  fs::write(
+    root.join("b/.git/worktrees/b-feature/gitdir"),
+    dir.join("b-feature/.git").to_str().unwrap(),
+  )
+  .unwrap();
+  fs::write(
+    root.join("b/.git/worktrees/b-feature/HEAD"),
+    "ref: refs/heads/feature",
+  )
+  .unwrap();
+
+  let roots = vec![
+    RepoRoot::from(root.clone()),
+    RepoRoot::new(dir.join("missing"), RootLayout::Ghq),
+  ];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let status = finder.init().await;
+
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+  assert_eq!(index.roots.len(), 1);
+  index.save(&dir.join("popi")).unwrap();
+
+  let loaded = RepoIndex::load(&dir.join("popi")).unwrap();
+  assert_eq!(loaded, index);
+  assert_eq!(loaded.repos().len(), 3);
+  let cached = loaded.repos_for(&roots, &options).unwrap();
+  assert_eq!(cached.repos.len(), 3);
+  // The missing root was never indexed, so it needs a full scan
+  assert_eq!(cached.uncovered, vec![roots[1].clone()]);
+  assert_eq!(
+    loaded.repos_for(
+      &roots[..1],
+      &FinderOptions {
+        submodules: true,
+        ..Default::default()
+      }
+    ),
+    None
+  );
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+// One root gone for a while must not cost the others their instant startup
+#[tokio::test]
+async fn use_index_with_a_root_missing() {
+  let dir = temp_fixture("index_missing_1");
+  fs::create_dir_all(dir.join("repos/a")).unwrap();
+  let roots = vec![
+    RepoRoot::from(dir.join("mount")),
+    RepoRoot::from(dir.join("repos")),
+  ];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let status = finder.init().await;
+  assert_eq!(status.paths_not_found, vec![dir.join("mount")]);
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+  index.save(&dir.join("popi")).unwrap();
+
+  let loaded = RepoIndex::load(&dir.join("popi")).unwrap();
+  let cached = loaded.repos_for(&roots, &options).unwrap();
+  assert_eq!(
+    cached
+      .repos
+      .iter()
+      .map(|repo| repo.path.clone())
+      .collect::<Vec<PathBuf>>(),
+    vec![dir.join("repos/a")]
+  );
+  assert_eq!(cached.uncovered, vec![roots[0].clone()]);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn revalidate_changed_roots() {
+  let dir = temp_fixture("index_2");
+  let root_a = dir.join("repos-A");
+  let root_b = dir.join("repos-B");
+  fs::create_dir_all(root_a.join("a")).unwrap();
+  fs::create_dir_all(root_b.join("b")).unwrap();
+
+  let roots = vec![
+    RepoRoot::from(root_a.clone()),
+    RepoRoot::from(root_b.clone()),
+  ];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let status = finder.init().await;
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+
+  assert_eq!(index.revalidate(&roots, &options, None).await.index, None);
+
+  fs::create_dir_all(root_b.join("c")).unwrap();
+  touch_dir(&root_b);
+  let revalidated = index
+    .revalidate(&roots, &options, None)
+    .await
+    .index
+    .unwrap();
+  let mut names = revalidated
+    .repos()
+    .into_iter()
+    .map(|repo| repo.name.to_string_lossy().into_owned())
+    .collect::<Vec<String>>();
+  names.sort();
+  assert_eq!(names, vec!["a", "b", "c"]);
+  // Unchanged roots are carried over as they are
+  assert_eq!(revalidated.roots[0], index.roots[0]);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn report_root_statuses_from_warm_index() {
+  let dir = temp_fixture("index_status_1");
+  fs::create_dir_all(dir.join("repos-A/a")).unwrap();
+  fs::create_dir_all(dir.join("repos-B/b")).unwrap();
+  let roots = vec![
+    RepoRoot::from(dir.join("repos-A")),
+    RepoRoot::from(dir.join("repos-B")),
+  ];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let mut status = finder.init().await;
+  // As if part of the first root could not be read
+  status
+    .paths_permission_denied
+    .push(dir.join("repos-A/locked"));
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+  index.save(&dir.join("popi")).unwrap();
+  let loaded = RepoIndex::load(&dir.join("popi")).unwrap();
+  assert_eq!(loaded, index);
+
+  fs::remove_dir_all(dir.join("repos-B")).unwrap();
+  let revalidation = loaded.revalidate(&roots, &options, None).await;
+  let locked = ScanError {
+    path: dir.join("repos-A/locked"),
+    kind: ScanErrorKind::PermissionDenied,
+  };
+  match &revalidation.statuses[..] {
+    [(a_path, RepoStatus::Found(repos, errors)), (b_path, RepoStatus::NotFound(_))] => {
+      assert_eq!(a_path, &dir.join("repos-A"));
+      assert_eq!(repos.len(), 1);
+      // Unchanged roots still report what could not be read
+      assert_eq!(errors, &vec![locked]);
+      assert_eq!(b_path, &dir.join("repos-B"));
+    }
+    statuses => panic!("unexpected statuses: {:?}", statuses),
+  }
+  assert_eq!(revalidation.index.unwrap().repos().len(), 1);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn send_root_statuses_as_they_are_revalidated() {
+  let dir = temp_fixture("index_status_2");
+  for i in 0..2000 {
+    fs::create_dir_all(dir.join(format!("slow/repo-{}", i))).unwrap();
+  }
+  fs::create_dir_all(dir.join("fast/a")).unwrap();
+  // Listed first, a root by root revalidation would send its status first
+  let roots = vec![
+    RepoRoot::from(dir.join("slow")),
+    RepoRoot::from(dir.join("fast")),
+  ];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let status = finder.init().await;
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+
+  fs::create_dir_all(dir.join("slow/repo-new")).unwrap();
+  touch_dir(&dir.join("slow"));
+  let (reposupdate_tx, mut reposupdate_rx) = mpsc::channel::<ReposUpdate>(20);
+  let revalidation = index
+    .revalidate(&roots, &options, Some(reposupdate_tx))
+    .await;
+  let mut sent: Vec<PathBuf> = vec![];
+  while let Some(ReposUpdate::RootScanned { root_path, .. }) = reposupdate_rx.recv().await {
+    sent.push(root_path);
+  }
+  assert_eq!(sent, vec![dir.join("fast"), dir.join("slow")]);
+  // Still in the configured order
+  assert_eq!(revalidation.statuses[0].0, dir.join("slow"));
+  assert_eq!(revalidation.index.unwrap().repos().len(), 2002);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rescan_timed_out_roots_on_next_launch() {
+  let dir = temp_fixture("index_timeout_1");
+  fs::create_dir_all(dir.join("fast/a")).unwrap();
+  // Every entry is checked for worktrees, so this takes far longer than the timeout
+  for i in 0..2000 {
+    fs::create_dir_all(dir.join(format!("slow/repo-{}", i))).unwrap();
+  }
+  let roots = vec![
+    RepoRoot::from(dir.join("fast")),
+    RepoRoot {
+      timeout: Some(Duration::from_millis(1)),
+      ..RepoRoot::from(dir.join("slow"))
+    },
+  ];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let status = finder.init().await;
+  assert_eq!(status.paths_timed_out, vec![dir.join("slow")]);
+
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+  index.save(&dir.join("popi")).unwrap();
+  let loaded = RepoIndex::load(&dir.join("popi")).unwrap();
+  // Not an empty list, the slow root has to be scanned again
+  let cached = loaded.repos_for(&roots, &options).unwrap();
+  assert_eq!(cached.repos.len(), 1);
+  assert_eq!(cached.uncovered, vec![roots[1].clone()]);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn keep_non_utf8_names_in_index() {
+  use std::ffi::OsStr;
+  use std::os::unix::ffi::OsStrExt;
+
+  let dir = temp_fixture("index_non_utf8_1");
+  let root = dir.join(OsStr::from_bytes(b"r\xf4ots"));
+  let name = OsStr::from_bytes(b"caf\xe9");
+  if fs::create_dir_all(root.join(name)).is_err() {
+    fs::remove_dir_all(&dir).unwrap();
+    return;
+  }
+
+  let roots = vec![RepoRoot::from(root.clone())];
+  let options = FinderOptions::default();
+  let mut finder = ReposFinder::new_with_options(roots.clone(), options.clone());
+  let status = finder.init().await;
+
+  let index = RepoIndex::build(&roots, &options, &finder.listup_repos(), &status).await;
+  index.save(&dir.join("popi")).unwrap();
+  let loaded = RepoIndex::load(&dir.join("popi")).unwrap();
+  assert_eq!(loaded, index);
+  let repos = loaded.repos_for(&roots, &options).unwrap().repos;
+  assert_eq!(repos[0].name, name);
+  assert_eq!(repos[0].path, root.join(name));
+  fs::remove_dir_all(&dir).unwrap();
+}
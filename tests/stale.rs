@@ -0,0 +1,68 @@
+extern crate popi;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use popi::config::RepoRoot;
+use popi::finder::ReposFinder;
+use popi::stale::stale_repos;
+
+mod common;
+use common::temp_fixture;
+
+fn git_repo(path: &Path, branch: &str, last_active: i64) {
+  fs::create_dir_all(path.join(".git/logs")).unwrap();
+  fs::write(
+    path.join(".git/HEAD"),
+    format!("ref: refs/heads/{}\n", branch),
+  )
+  .unwrap();
+  fs::write(
+    path.join(".git/logs/HEAD"),
+    format!(
+      "{0} {0} Someone <someone@example.com> {1} +0000\tcommit (initial): start\n",
+      "0".repeat(40),
+      last_active
+    ),
+  )
+  .unwrap();
+}
+
+#[tokio::test]
+async fn list_stale_repos_by_size() {
+  let dir = temp_fixture("stale_1");
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap()
+    .as_secs() as i64;
+  let day = 24 * 60 * 60;
+  git_repo(&dir.join("old-small"), "main", now - 200 * day);
+  git_repo(&dir.join("old-large"), "legacy", now - 100 * day);
+  fs::write(dir.join("old-large/dump.bin"), vec![1u8; 256 * 1024]).unwrap();
+  git_repo(&dir.join("fresh"), "main", now - day);
+
+  let mut finder = ReposFinder::new(vec![RepoRoot::from(dir.clone())]);
+  finder.init().await;
+
+  let stale = stale_repos(&finder, 90, now).await;
+  let summary = stale
+    .iter()
+    .map(|repo| {
+      let git = repo.git.as_ref().unwrap();
+      (repo.name.as_str(), git.branch.as_deref(), git.unpushed)
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(
+    summary,
+    vec![
+      ("old-large", Some("legacy"), None),
+      ("old-small", Some("main"), None),
+    ]
+  );
+  assert!(stale[0].size >= 256 * 1024);
+  // Nothing is pushed without an upstream
+  assert!(stale[0].git.as_ref().unwrap().has_unsaved_work());
+
+  assert_eq!(stale_repos(&finder, 150, now).await.len(), 1);
+  fs::remove_dir_all(&dir).unwrap();
+}
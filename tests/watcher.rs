@@ -0,0 +1,82 @@
+extern crate popi;
+use std::fs;
+use std::time::Duration;
+
+use popi::config::{FinderOptions, RepoRoot, RootLayout};
+use popi::finder::ReposUpdate;
+use popi::watcher::spawn_watcher;
+use tokio::sync::mpsc;
+
+mod common;
+use common::temp_fixture;
+
+async fn next_names(reposupdate_rx: &mut mpsc::Receiver<ReposUpdate>) -> Vec<String> {
+  let update = tokio::time::timeout(Duration::from_secs(10), reposupdate_rx.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  let ReposUpdate::RootReplaced { repos, .. } = update else {
+    panic!("unexpected update: {:?}", update);
+  };
+  let mut names = repos
+    .into_iter()
+    .map(|repo| repo.display_name())
+    .collect::<Vec<String>>();
+  names.sort();
+  names
+}
+
+// Watches are registered by the spawned task, give it a moment
+async fn wait_for_watches() {
+  tokio::time::sleep(Duration::from_millis(300)).await;
+}
+
+#[tokio::test]
+async fn watch_flat_root() {
+  let dir = temp_fixture("watcher_1");
+  fs::create_dir_all(dir.join("a")).unwrap();
+  let (reposupdate_tx, mut reposupdate_rx) = mpsc::channel::<ReposUpdate>(20);
+  let worker = spawn_watcher(
+    vec![RepoRoot::from(dir.clone())],
+    FinderOptions::default(),
+    reposupdate_tx,
+  )
+  .unwrap();
+  wait_for_watches().await;
+
+  fs::create_dir_all(dir.join("b")).unwrap();
+  assert_eq!(next_names(&mut reposupdate_rx).await, vec!["a", "b"]);
+
+  fs::remove_dir_all(dir.join("a")).unwrap();
+  assert_eq!(next_names(&mut reposupdate_rx).await, vec!["b"]);
+
+  worker.abort();
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn watch_new_owner_in_ghq_root() {
+  let dir = temp_fixture("watcher_2");
+  fs::create_dir_all(dir.join("github.com/aspulse/popi")).unwrap();
+  let (reposupdate_tx, mut reposupdate_rx) = mpsc::channel::<ReposUpdate>(20);
+  let worker = spawn_watcher(
+    vec![RepoRoot::new(dir.clone(), RootLayout::Ghq)],
+    FinderOptions::default(),
+    reposupdate_tx,
+  )
+  .unwrap();
+  wait_for_watches().await;
+
+  fs::create_dir_all(dir.join("github.com/someone")).unwrap();
+  assert_eq!(next_names(&mut reposupdate_rx).await, vec!["aspulse/popi"]);
+
+  // The owner directory created above is watched after the rescan
+  fs::create_dir_all(dir.join("github.com/someone/api")).unwrap();
+  assert_eq!(
+    next_names(&mut reposupdate_rx).await,
+    vec!["aspulse/popi", "someone/api"]
+  );
+
+  worker.abort();
+  fs::remove_dir_all(&dir).unwrap();
+}